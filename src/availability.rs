@@ -0,0 +1,134 @@
+use icalendar::{Component, Event, Property};
+
+use crate::structs::{ConfigInfo, Gap};
+
+/// Sorts busy `(start, end)` intervals and merges the ones that overlap or
+/// touch. Empty intervals are dropped.
+pub fn merge_busy(intervals: impl IntoIterator<Item = (i64, i64)>) -> Vec<(i64, i64)> {
+    let mut intervals: Vec<(i64, i64)> = intervals.into_iter().filter(|(s, e)| s < e).collect();
+    intervals.sort();
+    let mut merged: Vec<(i64, i64)> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            // an event inside a longer one must not end the longer one early
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// The free time between `from` and `until` around the busy intervals,
+/// including the stretches before the first and after the last of them.
+pub fn free_between(busy: impl IntoIterator<Item = (i64, i64)>, from: i64, until: i64) -> Vec<Gap> {
+    let mut gaps = vec![];
+    let mut cursor = from;
+    for (start, end) in merge_busy(busy) {
+        if start >= until {
+            break;
+        }
+        if cursor < start {
+            gaps.push(Gap {
+                start: cursor,
+                end: start,
+            });
+        }
+        cursor = cursor.max(end);
+    }
+    if cursor < until {
+        gaps.push(Gap {
+            start: cursor,
+            end: until,
+        });
+    }
+    gaps
+}
+
+// the properties that decide whether an event takes up time, written back as they were
+const AVAILABILITY_PROPERTIES: [&str; 3] = ["TRANSP", "STATUS", "ATTENDEE"];
+
+/// Writes a property back out as a content line. Parameter values holding
+/// `:`, `;` or `,` are quoted, see RFC 5545 section 3.2.
+pub fn property_line(property: &Property) -> String {
+    let mut line = property.key().to_string();
+    // sorted, so the same event is always written the same way
+    let mut params: Vec<_> = property.params().values().collect();
+    params.sort_by_key(|p| p.key());
+    for param in params {
+        let value = param.value();
+        // a quoted value cannot hold a DQUOTE itself, so one is dropped
+        if value.contains([':', ';', ',']) {
+            line.push_str(&format!(";{}=\"{}\"", param.key(), value.replace('"', "")));
+        } else {
+            line.push_str(&format!(";{}={value}", param.key()));
+        }
+    }
+    format!("{line}:{}", property.value())
+}
+
+fn all_properties<'a>(event: &'a Event, key: &str) -> Vec<&'a Property> {
+    event
+        .properties()
+        .get(key)
+        .into_iter()
+        .chain(event.multi_properties().get(key).into_iter().flatten())
+        .collect()
+}
+
+/// The lines of the event that decide whether it takes up time, to keep
+/// them when the event is written back.
+pub fn availability_lines(event: &Event) -> Vec<String> {
+    AVAILABILITY_PROPERTIES
+        .iter()
+        .flat_map(|key| all_properties(event, key))
+        .map(property_line)
+        .collect()
+}
+
+// your PARTSTAT on the event, when you are one of its attendees
+fn my_partstat(event: &Event, email: &str) -> Option<String> {
+    let email = email.to_lowercase();
+    all_properties(event, "ATTENDEE")
+        .into_iter()
+        .find(|a| {
+            a.value()
+                .to_lowercase()
+                .trim_start_matches("mailto:")
+                .eq(&email)
+        })
+        .and_then(|a| a.params().get("PARTSTAT"))
+        .map(|p| p.value().to_uppercase())
+}
+
+/// Whether the event takes up your time: not when it is cancelled, declined
+/// or transparent, and for all-day and tentative events as
+/// `[availability]` says.
+pub fn is_busy(event: &Event, all_day: bool, config: &ConfigInfo) -> bool {
+    let availability = &config.availability;
+    let email = availability
+        .email
+        .clone()
+        .or(Some(config.caldav.cal_username.clone()).filter(|u| u.contains('@')));
+    let partstat = email.and_then(|email| my_partstat(event, &email));
+    let status = event.property_value("STATUS").map(str::to_uppercase);
+    let transp = event.property_value("TRANSP").map(str::to_uppercase);
+    if status.as_deref() == Some("CANCELLED") || partstat.as_deref() == Some("DECLINED") {
+        return false;
+    }
+    if transp.as_deref() == Some("TRANSPARENT") {
+        return false;
+    }
+    // all-day events are mostly reminders unless they say they block time
+    if all_day && transp.is_none() {
+        return availability.all_day == "busy";
+    }
+    let tentative = status.as_deref() == Some("TENTATIVE")
+        || matches!(
+            partstat.as_deref(),
+            Some("TENTATIVE") | Some("NEEDS-ACTION")
+        );
+    if tentative {
+        return availability.tentative == "busy";
+    }
+    true
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::structs::{Batching, Task, TimeBlock};
+
+fn common<T: PartialEq + Clone>(values: impl Iterator<Item = T>) -> Option<T> {
+    let mut values = values.peekable();
+    let first = values.peek()?.clone();
+    values.all(|v| v == first).then_some(first)
+}
+
+// the same members always get the same uid, so a replanned batch keeps its event
+fn batch_uid(members: &[Task]) -> String {
+    let mut uuids: Vec<&str> = members.iter().map(|t| t.uuid.as_str()).collect();
+    uuids.sort();
+    // FNV-1a, which unlike std's hasher is the same on every build
+    let hash = uuids
+        .join(",")
+        .bytes()
+        .fold(0xcbf29ce484222325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        });
+    format!("cronwave-batch-{hash:016x}")
+}
+
+fn batch_task(members: &[Task]) -> Task {
+    let names: Vec<&str> = members.iter().map(|t| t.description.as_str()).collect();
+    let first = &members[0];
+    Task {
+        id: 0,
+        uuid: batch_uid(members),
+        description: format!("admin batch: {}", names.join(", ")),
+        due: members.iter().map(|t| t.due).min().unwrap(),
+        estimated: members.iter().map(|t| t.estimated).sum(),
+        status: "pending".to_string(),
+        urgency: members.iter().map(|t| t.urgency).fold(f32::MIN, f32::max),
+        start: members.iter().filter_map(|t| t.start).max(),
+        project: common(members.iter().map(|t| t.project.clone())).flatten(),
+        tags: first
+            .tags
+            .iter()
+            .filter(|tag| members.iter().all(|t| t.tags.contains(tag)))
+            .cloned()
+            .collect(),
+        three_point: None,
+        energy: common(members.iter().map(|t| t.energy.clone())).flatten(),
+    }
+}
+
+/// With `batch_small` on, combines tasks shorter than `small_minutes` into
+/// admin batch tasks of up to `batch_minutes`, earliest due first. Only tasks
+/// with the same `rules`, such as the time windows and place that apply to
+/// them, share a batch. Returns the tasks to place and the member uuids of
+/// each batch, keyed by its uuid.
+pub fn combine<K: Eq + Hash>(
+    tasks: Vec<Task>,
+    config: &Batching,
+    rules: impl Fn(&Task) -> K,
+) -> (Vec<Task>, HashMap<String, Vec<String>>) {
+    let mut batches = HashMap::new();
+    if !config.batch_small {
+        return (tasks, batches);
+    }
+    let (mut small, mut output): (Vec<Task>, Vec<Task>) = tasks
+        .into_iter()
+        .partition(|t| t.estimated < config.small_minutes * 60);
+    small.sort_by_key(|t| t.due);
+    let mut groups: Vec<(K, Vec<Task>)> = vec![];
+    for task in small {
+        let key = rules(&task);
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, group)) => group.push(task),
+            None => groups.push((key, vec![task])),
+        }
+    }
+
+    let mut chunk: Vec<Task> = vec![];
+    let mut flush = |chunk: &mut Vec<Task>, output: &mut Vec<Task>| {
+        // a lone small task is not worth a batch
+        if chunk.len() > 1 {
+            let task = batch_task(chunk);
+            batches.insert(
+                task.uuid.clone(),
+                chunk.iter().map(|t| t.uuid.clone()).collect(),
+            );
+            output.push(task);
+        } else {
+            output.append(chunk);
+        }
+        chunk.clear();
+    };
+    for (_, group) in groups {
+        for task in group {
+            let length: i64 = chunk.iter().map(|t| t.estimated).sum();
+            if length + task.estimated > config.batch_minutes * 60 {
+                flush(&mut chunk, &mut output);
+            }
+            chunk.push(task);
+        }
+        flush(&mut chunk, &mut output);
+    }
+    (output, batches)
+}
+
+/// Records the members on the blocks placed for each batch.
+pub fn label(blocks: &mut [TimeBlock], batches: &HashMap<String, Vec<String>>) {
+    for block in blocks {
+        if let Some(members) = batches.get(&block.uid) {
+            block.batch = members.clone();
+        }
+    }
+}
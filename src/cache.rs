@@ -0,0 +1,147 @@
+use chrono::{Local, TimeZone, Utc};
+use cronwave::structs::ConfigInfo;
+use cronwave::{Error, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+/// What is known about a downloaded calendar, kept next to it as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub url: String,
+    pub fetched_at: i64,
+    pub etag: Option<String>,
+}
+
+// the configured cache dir, or $XDG_CACHE_HOME/cronwave with a directory per named profile
+pub fn dir(config: &ConfigInfo) -> Result<PathBuf> {
+    let dir = match &config.cache.dir {
+        Some(dir) => PathBuf::from(dir),
+        None => {
+            let cache = ProjectDirs::from("", "", "cronwave")
+                .ok_or_else(|| Error::Config("no home directory to put the cache in".to_string()))?
+                .cache_dir()
+                .to_path_buf();
+            match &config.profile {
+                Some(profile) => cache.join(profile),
+                None => cache,
+            }
+        }
+    };
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+// one file name per calendar url, readable enough to tell them apart by eye
+fn file_stem(url: &str) -> String {
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    url.trim_end_matches('/')
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+pub fn calendar_path(config: &ConfigInfo, url: &str) -> Result<PathBuf> {
+    Ok(dir(config)?.join(format!("{}.ics", file_stem(url))))
+}
+
+fn entry_path(config: &ConfigInfo, url: &str) -> Result<PathBuf> {
+    Ok(dir(config)?.join(format!("{}.json", file_stem(url))))
+}
+
+/// The metadata of a cached calendar, or None when it was never fetched or
+/// the calendar file is gone.
+pub fn entry(config: &ConfigInfo, url: &str) -> Option<Entry> {
+    if !calendar_path(config, url).ok()?.exists() {
+        return None;
+    }
+    let contents = fs::read_to_string(entry_path(config, url).ok()?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn write_entry(config: &ConfigInfo, entry: &Entry) -> Result<()> {
+    let json = serde_json::to_string_pretty(entry).map_err(std::io::Error::other)?;
+    fs::write(entry_path(config, &entry.url)?, json)?;
+    Ok(())
+}
+
+/// Stores a freshly downloaded calendar.
+pub fn store(config: &ConfigInfo, url: &str, etag: Option<String>, text: &str) -> Result<()> {
+    fs::write(calendar_path(config, url)?, text)?;
+    write_entry(
+        config,
+        &Entry {
+            url: url.to_string(),
+            fetched_at: Utc::now().timestamp(),
+            etag,
+        },
+    )
+}
+
+/// Marks a cached calendar as checked just now, for when the server says it
+/// has not changed.
+pub fn touch(config: &ConfigInfo, mut entry: Entry) -> Result<()> {
+    entry.fetched_at = Utc::now().timestamp();
+    write_entry(config, &entry)
+}
+
+fn entries(config: &ConfigInfo) -> Result<Vec<(Entry, u64)>> {
+    let mut entries = vec![];
+    for file in fs::read_dir(dir(config)?)? {
+        let path = file?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let Some(entry) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Entry>(&contents).ok())
+        else {
+            continue;
+        };
+        let size = fs::metadata(path.with_extension("ics")).map_or(0, |m| m.len());
+        entries.push((entry, size));
+    }
+    entries.sort_by(|a, b| a.0.url.cmp(&b.0.url));
+    Ok(entries)
+}
+
+/// Prints where the cache lives and every calendar in it.
+pub fn show(config: &ConfigInfo) -> Result<()> {
+    println!("cache: {}", dir(config)?.display());
+    let entries = entries(config)?;
+    if entries.is_empty() {
+        println!("no calendars cached");
+    }
+    for (entry, size) in entries {
+        let fetched = Local
+            .timestamp_opt(entry.fetched_at, 0)
+            .single()
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        println!("{}", entry.url);
+        println!("  fetched {fetched}, {size} bytes");
+        if let Some(etag) = entry.etag {
+            println!("  etag {etag}");
+        }
+    }
+    Ok(())
+}
+
+/// Deletes every cached calendar, so the next run downloads them again.
+pub fn clear(config: &ConfigInfo) -> Result<()> {
+    let dir = dir(config)?;
+    let mut removed = 0;
+    for file in fs::read_dir(&dir)? {
+        let path = file?.path();
+        if path
+            .extension()
+            .is_some_and(|ext| ext == "ics" || ext == "json")
+        {
+            fs::remove_file(path)?;
+            removed += 1;
+        }
+    }
+    println!("removed {removed} files from {}", dir.display());
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use chrono::{NaiveDateTime, NaiveTime, TimeZone, Utc};
+use icalendar::{CalendarDateTime, Component, DatePerhapsTime, Event, EventLike};
+use iso8601_duration::Duration;
+use std::str::FromStr;
+
+use crate::availability::{availability_lines, is_busy};
+use crate::dates::Tz;
+use crate::structs::{ConfigInfo, TimeBlock};
+
+// dates and floating times are read in `zone`, a TZID chrono-tz knows wins over it
+fn to_timestamp(date: DatePerhapsTime, zone: Tz) -> Result<i64, String> {
+    let local = |naive: NaiveDateTime, zone: Tz| {
+        zone.from_local_datetime(&naive)
+            .earliest()
+            .map(|dt| dt.timestamp())
+            .ok_or_else(|| format!("{naive} does not exist in {zone}"))
+    };
+    match date {
+        DatePerhapsTime::Date(date) => local(date.and_time(NaiveTime::MIN), zone),
+        DatePerhapsTime::DateTime(dt) => match dt {
+            CalendarDateTime::Floating(float) => local(float, zone),
+            CalendarDateTime::Utc(utc) => Ok(utc.timestamp()),
+            CalendarDateTime::WithTimezone { date_time, tzid } => {
+                let zone = tzid.parse::<chrono_tz::Tz>().map(Tz::Tz).unwrap_or(zone);
+                local(date_time, zone)
+            }
+        },
+    }
+}
+
+/// Reads one VEVENT into a block. Dates and floating times are in the
+/// profile's zone.
+pub fn parse_event(event: &Event, config: &ConfigInfo) -> Result<TimeBlock, String> {
+    let start = event.get_start().ok_or("no DTSTART")?;
+    let all_day = matches!(start, DatePerhapsTime::Date(_));
+    let zone = config.planning.zone().map_err(|e| e.to_string())?;
+    let dtstart = to_timestamp(start, zone)?;
+    let duration = match event.properties().get("DURATION") {
+        Some(dur) => {
+            let seconds = dur
+                .value()
+                .parse::<Duration>()
+                .ok()
+                .and_then(|d| d.num_seconds())
+                .ok_or_else(|| format!("invalid DURATION {}", dur.value()))?;
+            Some(seconds as i64)
+        }
+        None => None,
+    };
+    let dtend = event
+        .get_end()
+        .map(|end| to_timestamp(end, zone))
+        .transpose()?;
+    let uid = event.get_uid().ok_or("no UID")?;
+    let task_uuid = event
+        .properties()
+        .get("X-CRONWAVE-TASK")
+        .map(|p| p.value().to_string());
+    let locked = event
+        .properties()
+        .get("X-CRONWAVE-LOCKED")
+        .is_some_and(|p| p.value().eq_ignore_ascii_case("TRUE"));
+    let batch = event
+        .properties()
+        .get("X-CRONWAVE-BATCH")
+        .map(|p| p.value().split(',').map(str::to_string).collect())
+        .unwrap_or_default();
+    let location = event.get_location().map(str::to_string);
+    let busy = is_busy(event, all_day, config);
+    let availability = availability_lines(event);
+    let summary = event.get_summary().unwrap_or_default();
+    let dtstamp = event.get_timestamp().unwrap_or_else(Utc::now);
+    let rrule = match event.properties().get("RRULE") {
+        Some(rrule) => {
+            let rrule = rrule::RRule::from_str(rrule.value())
+                .map_err(|e| format!("invalid RRULE {}: {e}", rrule.value()))?;
+            let time = zone.timestamp_opt(dtstart, 0).unwrap();
+            Some(
+                rrule
+                    .validate(time)
+                    .map_err(|e| format!("invalid RRULE: {e}"))?,
+            )
+        }
+        None => None,
+    };
+    Ok(TimeBlock {
+        dtstart,
+        dtend,
+        summary: summary.to_string(),
+        rrule,
+        uid: uid.to_string(),
+        duration,
+        dtstamp,
+        task_uuid,
+        locked,
+        batch,
+        location,
+        busy,
+        availability,
+        foreign: false,
+        all_day,
+    })
+}
+
+/// The text of a calendar holding the blocks, with floating times in `zone`
+/// that `parse_event` reads back in the same zone. All-day blocks are
+/// written as dates.
+pub fn write_calendar(blocks: &[TimeBlock], zone: Tz) -> String {
+    let mut string_vec = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//cronwave//cronwave//EN".to_string(),
+    ];
+    let local = |timestamp: i64, format: &str| {
+        zone.timestamp_opt(timestamp, 0)
+            .unwrap()
+            .format(format)
+            .to_string()
+    };
+    let floating = |timestamp: i64| local(timestamp, "%Y%m%dT%H%M%S");
+    let date = |timestamp: i64| local(timestamp, "%Y%m%d");
+
+    for event in blocks {
+        string_vec.push("BEGIN:VEVENT".to_string());
+        if event.all_day {
+            string_vec.push(format!("DTSTART;VALUE=DATE:{}", date(event.dtstart)));
+        } else {
+            string_vec.push(format!("DTSTART:{}", floating(event.dtstart)));
+        }
+
+        string_vec.push(format!("UID:{}", event.uid));
+        string_vec.push(format!(
+            "DTSTAMP:{}",
+            event.dtstamp.format("%Y%m%dT%H%M%SZ")
+        ));
+        if let Some(dur) = event.duration.filter(|_| event.all_day) {
+            // a date can only last whole days
+            string_vec.push(format!("DURATION:P{}D", (dur / 86400).max(1)));
+        } else if let Some(dur) = event.duration {
+            let hours = chrono::Duration::seconds(dur).num_hours() * 3600;
+            let minutes = chrono::Duration::seconds(dur - hours).num_minutes();
+            if hours == 0 {
+                string_vec.push(format!(
+                    "DURATION:PT{}M",
+                    chrono::Duration::seconds(dur).num_minutes()
+                ));
+            } else if minutes == 0 {
+                string_vec.push(format!(
+                    "DURATION:PT{}H",
+                    chrono::Duration::seconds(dur).num_hours()
+                ));
+            } else {
+                string_vec.push(format!(
+                    "DURATION:PT{}H{}M",
+                    chrono::Duration::seconds(dur).num_hours(),
+                    chrono::Duration::seconds(dur - hours).num_minutes()
+                ));
+            }
+        }
+        if let Some(dtend) = event.dtend {
+            if event.all_day {
+                string_vec.push(format!("DTEND;VALUE=DATE:{}", date(dtend)));
+            } else {
+                string_vec.push(format!("DTEND:{}", floating(dtend)));
+            }
+        };
+        string_vec.push(format!("SUMMARY:{}", event.summary));
+        if let Some(task_uuid) = &event.task_uuid {
+            string_vec.push(format!("X-CRONWAVE-TASK:{task_uuid}"));
+        }
+        if event.locked {
+            string_vec.push("X-CRONWAVE-LOCKED:TRUE".to_string());
+        }
+        string_vec.extend(event.availability.iter().cloned());
+        if let Some(location) = &event.location {
+            string_vec.push(format!("LOCATION:{location}"));
+        }
+        if !event.batch.is_empty() {
+            string_vec.push(format!("X-CRONWAVE-BATCH:{}", event.batch.join(",")));
+        }
+        if let Some(rrule) = &event.rrule {
+            string_vec.push(format!("RRULE:{rrule}"));
+        }
+        string_vec.push("END:VEVENT".to_string());
+    }
+    string_vec.push("END:VCALENDAR".to_string());
+    // content lines end in CRLF, RFC 5545 section 3.1
+    string_vec.join("\r\n") + "\r\n"
+}
@@ -0,0 +1,26 @@
+use chrono::Utc;
+
+/// Where planning gets the current time from, so a plan can be replayed as of
+/// any moment.
+pub trait Clock {
+    /// The current time as a unix timestamp.
+    fn now(&self) -> i64;
+}
+
+/// The real wall clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        Utc::now().timestamp()
+    }
+}
+
+/// A clock stopped at a fixed timestamp.
+pub struct FixedClock(pub i64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> i64 {
+        self.0
+    }
+}
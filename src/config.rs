@@ -1,7 +1,132 @@
+use cronwave::credentials::PASSWORD_ENV;
+use cronwave::schema::{self, Resolved, Source};
 use cronwave::structs::ConfigInfo;
+use cronwave::Error;
+use std::path::PathBuf;
 
-pub fn get_config() -> Result<ConfigInfo, Box<dyn std::error::Error>> {
-    let cfg: ConfigInfo = confy::load("cronwave", None)?;
-    dbg!(&cfg);
-    Ok(cfg)
+// the file confy keeps the default profile in, without the .toml
+const DEFAULT_PROFILE: &str = "default-config";
+
+// reads the named profile's file, creating it with the defaults when it is
+// missing, like confy does, and migrating an older one in place with a
+// backup of the original next to it
+fn read_file(profile: Option<&str>) -> cronwave::Result<(PathBuf, String)> {
+    let path = confy::get_configuration_file_path("cronwave", profile)
+        .map_err(|e| Error::Config(e.to_string()))?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            confy::store_path(&path, ConfigInfo::default())
+                .map_err(|e| Error::Config(e.to_string()))?;
+            std::fs::read_to_string(&path)?
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let in_file = |e: Error| match e {
+        Error::Config(message) => Error::Config(format!("{}: {message}", path.display())),
+        other => other,
+    };
+    let text = match schema::migrate_text(&text).map_err(in_file)? {
+        Some((from, migrated)) => {
+            let backup = path.with_extension(format!("toml.v{from}"));
+            std::fs::copy(&path, &backup)?;
+            std::fs::write(&path, &migrated)?;
+            eprintln!(
+                "migrated {} from version {from} to {}, the old file is {}",
+                path.display(),
+                schema::VERSION,
+                backup.display()
+            );
+            migrated
+        }
+        None => text,
+    };
+    // checked on its own first so mistakes in the file come with its path and line
+    schema::parse(&text).map_err(in_file)?;
+    Ok((path, text))
+}
+
+// the config of a profile with the CRONWAVE_* environment and `--set` flags on top
+fn resolve(profile: Option<&str>, flags: &[String]) -> cronwave::Result<(PathBuf, Resolved)> {
+    let (path, text) = read_file(profile)?;
+    let env: Vec<(String, String)> = std::env::vars()
+        .filter(|(name, _)| name.starts_with(schema::ENV_PREFIX) && name != PASSWORD_ENV)
+        .collect();
+    let mut resolved = schema::resolve(&text, &env, flags)?;
+    resolved.config.profile = profile.map(str::to_string);
+    Ok((path, resolved))
+}
+
+/// Loads the named profile, or the default one when `profile` is None, with
+/// `CRONWAVE_*` environment variables and then `flags` (`key=value`) applied
+/// over the file.
+pub fn get_config(profile: Option<&str>, flags: &[String]) -> cronwave::Result<ConfigInfo> {
+    Ok(resolve(profile, flags)?.1.config)
+}
+
+/// Prints the values the config file sets, or with `resolved` every effective
+/// value and where it came from. Passwords are never printed.
+pub fn show(profile: Option<&str>, flags: &[String], resolved: bool) -> cronwave::Result<()> {
+    let (path, config) = resolve(profile, flags)?;
+    println!("# {}", path.display());
+    for (key, value, source) in config.values {
+        if !resolved && source != Source::File {
+            continue;
+        }
+        let value = match value.as_str() {
+            Some(secret) if schema::is_secret(&key) && !secret.is_empty() => {
+                "\"<redacted>\"".to_string()
+            }
+            _ => value.to_string(),
+        };
+        if resolved {
+            println!("{key} = {value}  # {source}");
+        } else {
+            println!("{key} = {value}");
+        }
+    }
+    if resolved && std::env::var_os(PASSWORD_ENV).is_some() {
+        println!("# the CalDAV password comes from {PASSWORD_ENV}");
+    }
+    Ok(())
+}
+
+/// Every profile that has a config file, the default one first.
+pub fn profiles() -> cronwave::Result<Vec<Option<String>>> {
+    let config_path = confy::get_configuration_file_path("cronwave", None)
+        .map_err(|e| Error::Config(e.to_string()))?;
+    let Some(dir) = config_path.parent() else {
+        return Ok(vec![None]);
+    };
+    let mut profiles = vec![];
+    for file in std::fs::read_dir(dir)? {
+        let path = file?.path();
+        if path.extension().is_none_or(|ext| ext != "toml") {
+            continue;
+        }
+        match path.file_stem().and_then(|stem| stem.to_str()) {
+            Some(DEFAULT_PROFILE) => profiles.push(None),
+            Some(name) => profiles.push(Some(name.to_string())),
+            None => (),
+        }
+    }
+    profiles.sort();
+    Ok(profiles)
+}
+
+// files cronwave keeps between runs live next to the confy config, in a
+// directory of their own for a named profile
+pub fn state_file(
+    name: &str,
+    profile: Option<&str>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let config_path = confy::get_configuration_file_path("cronwave", None)?;
+    let path = match profile {
+        Some(profile) => config_path.with_file_name(profile).join(name),
+        None => config_path.with_file_name(name),
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    Ok(path)
 }
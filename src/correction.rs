@@ -0,0 +1,44 @@
+use crate::structs::{EffortRecord, Task};
+
+// fewer finished tasks than this and a group's ratio is mostly noise
+pub const MIN_SAMPLES: usize = 3;
+
+/// The actual/estimated ratio over the records, clamped to 0.25-4, or None
+/// with fewer than `MIN_SAMPLES` of them.
+pub fn ratio<'a>(records: impl Iterator<Item = &'a EffortRecord>) -> Option<f64> {
+    let (count, estimated, actual) = records.fold((0, 0, 0), |(n, e, a), r| {
+        (n + 1, e + r.estimated, a + r.actual)
+    });
+    (count >= MIN_SAMPLES && estimated > 0)
+        .then(|| (actual as f64 / estimated as f64).clamp(0.25, 4.0))
+}
+
+/// How much to scale the task's estimate by: the average ratio of the
+/// groups it is in, its project and each of its tags, counting only groups
+/// with enough history. Falls back to the ratio over all records.
+pub fn factor(task: &Task, records: &[EffortRecord]) -> Option<f64> {
+    let project = ratio(
+        records
+            .iter()
+            .filter(|r| r.project.is_some() && r.project == task.project),
+    );
+    let tags = task
+        .tags
+        .iter()
+        .map(|tag| ratio(records.iter().filter(|r| r.tags.contains(tag))));
+    let groups: Vec<f64> = std::iter::once(project).chain(tags).flatten().collect();
+    if groups.is_empty() {
+        ratio(records.iter())
+    } else {
+        Some(groups.iter().sum::<f64>() / groups.len() as f64)
+    }
+}
+
+/// Scales each task's estimate by its `factor`.
+pub fn correct(tasks: &mut [Task], records: &[EffortRecord]) {
+    for task in tasks {
+        if let Some(factor) = factor(task, records) {
+            task.estimated = (task.estimated as f64 * factor).round() as i64;
+        }
+    }
+}
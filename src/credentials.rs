@@ -0,0 +1,65 @@
+use std::process::Command;
+
+use crate::structs::{Account, Secret};
+use crate::{Error, Result};
+
+/// Overrides every password setting in the config.
+pub const PASSWORD_ENV: &str = "CRONWAVE_CAL_PASS";
+
+fn password_from_command(command: &str) -> Result<Secret> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .output()
+        .map_err(|e| Error::Config(format!("failed to run password_command: {e}")))?;
+    // stderr is left out, it may echo what the command read
+    if !output.status.success() {
+        return Err(Error::Config(format!(
+            "password_command exited with {}",
+            output.status
+        )));
+    }
+    let stdout = String::from_utf8(output.stdout)
+        .map_err(|_| Error::Config("password_command printed invalid UTF-8".to_string()))?;
+    // like git credential helpers, only the first line is the password
+    Ok(Secret::new(stdout.lines().next().unwrap_or_default()))
+}
+
+fn password_from_file(path: &str) -> Result<Secret> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o077 != 0 {
+            return Err(Error::Config(format!(
+                "password_file {path} can be read by others, run chmod 600 {path}"
+            )));
+        }
+    }
+    let contents = std::fs::read_to_string(path)?;
+    Ok(Secret::new(contents.lines().next().unwrap_or_default()))
+}
+
+/// The CalDAV password, from [`PASSWORD_ENV`], `password_command`,
+/// `password_file` or `cal_pass`, in that order.
+pub fn resolve_password(account: &Account) -> Result<Secret> {
+    resolve_password_from(account, std::env::var(PASSWORD_ENV).ok())
+}
+
+/// Like [`resolve_password`], with `env` standing in for the environment.
+pub fn resolve_password_from(account: &Account, env: Option<String>) -> Result<Secret> {
+    if let Some(password) = env {
+        return Ok(Secret::new(password));
+    }
+    let password = match (&account.password_command, &account.password_file) {
+        (Some(command), _) => password_from_command(command)?,
+        (None, Some(path)) => password_from_file(path)?,
+        (None, None) => account.cal_pass.clone(),
+    };
+    if password.is_empty() {
+        return Err(Error::Config(format!(
+            "no CalDAV password, set password_command, password_file or {PASSWORD_ENV}"
+        )));
+    }
+    Ok(password)
+}
@@ -0,0 +1,98 @@
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use regex::Regex;
+use std::str::FromStr;
+
+pub use rrule::Tz;
+
+/// Converts an ISO 8601 calendar, week or ordinal date into a `NaiveDate`.
+pub fn date_to_naive(date: iso8601::Date) -> Result<NaiveDate, String> {
+    let naive = match date {
+        iso8601::Date::YMD { year, month, day } => NaiveDate::from_ymd_opt(year, month, day),
+        iso8601::Date::Week { year, ww, d } => {
+            let weekday = match d {
+                1 => chrono::Weekday::Mon,
+                2 => chrono::Weekday::Tue,
+                3 => chrono::Weekday::Wed,
+                4 => chrono::Weekday::Thu,
+                5 => chrono::Weekday::Fri,
+                6 => chrono::Weekday::Sat,
+                7 => chrono::Weekday::Sun,
+                _ => return Err(format!("week day {d} is not between 1 and 7")),
+            };
+            NaiveDate::from_isoywd_opt(year, ww, weekday)
+        }
+        iso8601::Date::Ordinal { year, ddd } => NaiveDate::from_yo_opt(year, ddd),
+    };
+    naive.ok_or_else(|| format!("{date:?} is not a valid date"))
+}
+
+/// Parses a taskwarrior/ISO 8601 date or date-time into a unix timestamp.
+///
+/// Strings ending in `Z` or carrying an offset are exact instants. Anything
+/// without a zone designator is read as wall-clock time in `tz`, and a bare
+/// date means midnight.
+pub fn parse_datetime_in<Tz: TimeZone>(input: &str, tz: &Tz) -> Result<i64, String> {
+    let input = input.trim();
+    let (date_part, time_part) = match input.find(['T', 't']) {
+        Some(idx) => (&input[..idx], Some(&input[idx + 1..])),
+        None => (input, None),
+    };
+    let date = date_to_naive(parse_date(date_part)?)?;
+    let Some(time_part) = time_part else {
+        return local_timestamp(date.and_time(NaiveTime::MIN), tz);
+    };
+    let time = iso8601::Time::from_str(time_part)
+        .map_err(|e| format!("invalid time in '{input}': {e}"))?;
+    let naive_time =
+        NaiveTime::from_hms_milli_opt(time.hour, time.minute, time.second, time.millisecond)
+            .ok_or_else(|| format!("invalid time in '{input}'"))?;
+    let naive = date.and_time(naive_time);
+
+    if !time_part.contains(['Z', 'z', '+', '-']) {
+        return local_timestamp(naive, tz);
+    }
+    let offset_seconds = time.tz_offset_hours * 3600 + time.tz_offset_minutes * 60;
+    let offset = FixedOffset::east_opt(offset_seconds)
+        .ok_or_else(|| format!("invalid utc offset in '{input}'"))?;
+    offset
+        .from_local_datetime(&naive)
+        .single()
+        .map(|dt| dt.timestamp())
+        .ok_or_else(|| format!("invalid date-time '{input}'"))
+}
+
+/// The IANA time zone `name`, such as `Europe/Berlin`, or the system zone
+/// when `name` is empty.
+pub fn zone(name: &str) -> Result<Tz, String> {
+    if name.is_empty() {
+        return Ok(Tz::LOCAL);
+    }
+    name.parse::<chrono_tz::Tz>()
+        .map(Tz::Tz)
+        .map_err(|_| format!("unknown time zone '{name}'"))
+}
+
+fn parse_date(input: &str) -> Result<iso8601::Date, String> {
+    // the iso8601 crate stops at week 52, but long ISO years have a week 53
+    let week = Regex::new(r"^(\d{4})-?W(\d{2})-?(\d)$").unwrap();
+    if let Some(caps) = week.captures(input) {
+        return Ok(iso8601::Date::Week {
+            year: caps[1].parse().unwrap(),
+            ww: caps[2].parse().unwrap(),
+            d: caps[3].parse().unwrap(),
+        });
+    }
+    iso8601::Date::from_str(input).map_err(|e| format!("invalid date '{input}': {e}"))
+}
+
+// wall-clock times that fall in a DST gap are pushed forward past the gap
+fn local_timestamp<Tz: TimeZone>(naive: NaiveDateTime, tz: &Tz) -> Result<i64, String> {
+    let mut candidate = naive;
+    for _ in 0..4 {
+        if let Some(dt) = tz.from_local_datetime(&candidate).earliest() {
+            return Ok(dt.timestamp());
+        }
+        candidate += chrono::Duration::minutes(30);
+    }
+    Err(format!("{naive} does not exist in the local time zone"))
+}
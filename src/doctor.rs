@@ -0,0 +1,228 @@
+use chrono::Local;
+use cronwave::credentials;
+use cronwave::dates::zone;
+use cronwave::schema;
+use cronwave::structs::ConfigInfo;
+use reqwest::blocking::Client;
+use reqwest::header::HeaderValue;
+use reqwest::{Method, Url};
+use std::process::Command;
+
+use crate::{cache, config, ical, init};
+
+// counts the failed checks, so the exit code can tell whether all is well
+#[derive(Default)]
+struct Report {
+    failed: usize,
+}
+
+impl Report {
+    fn pass(&self, check: &str, detail: impl std::fmt::Display) {
+        println!("ok    {check}: {detail}");
+    }
+
+    fn fail(&mut self, check: &str, detail: impl std::fmt::Display, fix: &str) {
+        self.failed += 1;
+        println!("FAIL  {check}: {detail}");
+        println!("      {fix}");
+    }
+}
+
+fn task_output(args: &[&str]) -> Option<String> {
+    let output = Command::new("task").args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn check_taskwarrior(report: &mut Report) {
+    let Some(version) = task_output(&["--version"]) else {
+        report.fail(
+            "taskwarrior",
+            "could not run `task`",
+            "install taskwarrior and make sure `task` is on your PATH",
+        );
+        return;
+    };
+    report.pass("taskwarrior", format!("version {version}"));
+
+    match task_output(&["_get", "rc.uda.estimated.type"]) {
+        Some(kind) if !kind.is_empty() => report.pass("estimated UDA", format!("type {kind}")),
+        _ => report.fail(
+            "estimated UDA",
+            "not defined",
+            "add `uda.estimated.type=string` to your .taskrc",
+        ),
+    }
+
+    match ical::export(&["status:pending", "estimated.any:"]) {
+        Ok(raw) => {
+            let total = raw.len();
+            let broken: Vec<String> = raw
+                .into_iter()
+                .filter_map(|task| ical::parse_task(task).err())
+                .collect();
+            if broken.is_empty() {
+                report.pass("task export", format!("parsed all {total} pending tasks"));
+            } else {
+                report.fail(
+                    "task export",
+                    format!("{} of {total} pending tasks can't be read", broken.len()),
+                    &broken.join("\n      "),
+                );
+            }
+        }
+        Err(e) => report.fail("task export", e, "check that `task export` works"),
+    }
+}
+
+fn check_caldav(report: &mut Report, config_data: &ConfigInfo) {
+    let password = match credentials::resolve_password(&config_data.caldav) {
+        Ok(password) => {
+            report.pass("password", "found");
+            password
+        }
+        Err(e) => {
+            report.fail("password", e, "see Credentials in the README");
+            return;
+        }
+    };
+    let Ok(url) = Url::parse(&config_data.caldav.cal_url) else {
+        report.fail(
+            "calendar",
+            format!("'{}' is not a URL", config_data.caldav.cal_url),
+            "set cal_url, or run `cronwave init`",
+        );
+        return;
+    };
+    let client = Client::new();
+    let options = client
+        .request(Method::OPTIONS, url.clone())
+        .basic_auth(&config_data.caldav.cal_username, Some(password.expose()))
+        .send();
+    let options = match options {
+        Ok(response) => {
+            report.pass(
+                "server",
+                format!("{} answered", url.host_str().unwrap_or("")),
+            );
+            response
+        }
+        Err(e) => {
+            report.fail("server", e, "check cal_url and your network connection");
+            return;
+        }
+    };
+    let dav = options
+        .headers()
+        .get_all("DAV")
+        .iter()
+        .filter_map(|value: &HeaderValue| value.to_str().ok())
+        .collect::<Vec<_>>()
+        .join(", ");
+    if dav.contains("calendar-access") {
+        report.pass("CalDAV", "the server supports calendar-access");
+    } else {
+        report.fail(
+            "CalDAV",
+            format!("the server does not advertise calendar-access (DAV: {dav})"),
+            "make sure cal_url points at a CalDAV calendar",
+        );
+    }
+    match init::propfind(
+        &client,
+        &url,
+        "0",
+        "<d:resourcetype/>",
+        &config_data.caldav,
+        &password,
+    ) {
+        Ok(_) => report.pass(
+            "login",
+            format!("signed in as {}", config_data.caldav.cal_username),
+        ),
+        Err(e) => report.fail("login", e, "check cal_username and the password"),
+    }
+}
+
+fn check_cache(report: &mut Report, config_data: &ConfigInfo) {
+    let written = cache::dir(config_data).and_then(|dir| {
+        let probe = dir.join(".doctor");
+        std::fs::write(&probe, b"")?;
+        std::fs::remove_file(&probe)?;
+        Ok(dir)
+    });
+    match written {
+        Ok(dir) => report.pass("cache", format!("{} is writable", dir.display())),
+        Err(e) => report.fail("cache", e, "set dir under [cache] to a writable directory"),
+    }
+}
+
+// chrono falls back to UTC when it can't find the zone, which shifts every event
+fn check_timezone(report: &mut Report, configured: &str) {
+    if !configured.is_empty() {
+        match zone(configured) {
+            Ok(_) => report.pass("time zone", format!("{configured} from planning.timezone")),
+            Err(e) => report.fail(
+                "time zone",
+                e,
+                "set planning.timezone to a name such as Europe/Berlin",
+            ),
+        }
+        return;
+    }
+    let offset = Local::now().format("%:z");
+    let zone = std::env::var("TZ")
+        .ok()
+        .filter(|tz| !tz.is_empty())
+        .or_else(|| {
+            std::fs::read_link("/etc/localtime")
+                .ok()
+                .map(|path| path.display().to_string())
+        });
+    match zone {
+        Some(zone) => report.pass("time zone", format!("{zone} (UTC{offset})")),
+        None if std::path::Path::new("/etc/localtime").exists() => {
+            report.pass("time zone", format!("UTC{offset}"))
+        }
+        None => report.fail(
+            "time zone",
+            "no TZ and no /etc/localtime, times are read as UTC",
+            "set TZ, for example TZ=Europe/Berlin",
+        ),
+    }
+}
+
+/// Checks taskwarrior, the config, the CalDAV server, the cache and the time
+/// zone, printing a line per check. Returns the exit code.
+pub fn doctor(profile: Option<&str>, flags: &[String]) -> i32 {
+    let mut report = Report::default();
+    check_taskwarrior(&mut report);
+    let fix = match confy::get_configuration_file_path("cronwave", profile) {
+        Ok(path) => format!("edit {}, or run `cronwave init`", path.display()),
+        Err(_) => "run `cronwave init`".to_string(),
+    };
+    let mut timezone = String::new();
+    match config::get_config(profile, flags) {
+        Ok(config_data) => {
+            timezone = config_data.planning.timezone.clone();
+            match schema::validate(&config_data) {
+                Ok(()) => {
+                    report.pass("config", "valid");
+                    check_caldav(&mut report, &config_data);
+                }
+                Err(e) => report.fail("config", e, &fix),
+            }
+            check_cache(&mut report, &config_data);
+        }
+        Err(e) => report.fail("config", e, &fix),
+    }
+    check_timezone(&mut report, &timezone);
+    if report.failed > 0 {
+        println!("{} checks failed", report.failed);
+        1
+    } else {
+        0
+    }
+}
@@ -0,0 +1,119 @@
+use std::str::FromStr;
+
+const MINUTE: f64 = 60.0;
+const HOUR: f64 = 3600.0;
+const DAY: f64 = 86400.0;
+const WEEK: f64 = 7.0 * DAY;
+// taskwarrior treats a month as 30 days and a year as 365
+const MONTH: f64 = 30.0 * DAY;
+const QUARTER: f64 = 91.0 * DAY;
+const YEAR: f64 = 365.0 * DAY;
+
+/// Parses an `estimated` value into seconds.
+///
+/// Accepts ISO 8601 durations (`PT1H30M`, `P2W`), taskwarrior's own duration
+/// words (`3days`, `2 weeks`, `weekly`) and shorthand like `90min`, `1.5h`,
+/// `2d` or `1h30m`. A duration that comes out as zero is an error.
+pub fn parse_duration(input: &str) -> Result<i64, String> {
+    match parse_seconds(input)? {
+        0 => Err(format!("duration '{input}' is zero")),
+        seconds => Ok(seconds),
+    }
+}
+
+fn parse_seconds(input: &str) -> Result<i64, String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err("empty duration".to_string());
+    }
+    if trimmed.starts_with(['P', 'p']) {
+        return iso8601::Duration::from_str(&trimmed.to_uppercase())
+            .map(iso8601_seconds)
+            .map_err(|e| format!("invalid ISO 8601 duration '{input}': {e}"));
+    }
+    let lower = trimmed.to_lowercase();
+    if let Some(seconds) = named(&lower) {
+        return Ok(seconds);
+    }
+
+    let mut total = 0.0;
+    let mut rest = lower.as_str();
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        if number_len == 0 {
+            return Err(format!("expected a number in duration '{input}'"));
+        }
+        let number: f64 = rest[..number_len]
+            .parse()
+            .map_err(|_| format!("invalid number in duration '{input}'"))?;
+        rest = rest[number_len..].trim_start();
+        let unit_len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        if unit_len == 0 {
+            return Err(format!("missing unit in duration '{input}'"));
+        }
+        let unit = unit_seconds(&rest[..unit_len])
+            .ok_or_else(|| format!("unknown unit '{}' in duration '{input}'", &rest[..unit_len]))?;
+        total += number * unit;
+        rest = rest[unit_len..].trim_start_matches([' ', ',']);
+    }
+    Ok(total.round() as i64)
+}
+
+fn unit_seconds(unit: &str) -> Option<f64> {
+    let seconds = match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => 1.0,
+        "m" | "min" | "mins" | "minute" | "minutes" => MINUTE,
+        "h" | "hr" | "hrs" | "hour" | "hours" => HOUR,
+        "d" | "day" | "days" => DAY,
+        "w" | "wk" | "wks" | "week" | "weeks" => WEEK,
+        "mo" | "mos" | "mth" | "mths" | "mnths" | "month" | "months" => MONTH,
+        "q" | "qtr" | "qtrs" | "quarter" | "quarters" => QUARTER,
+        "y" | "yr" | "yrs" | "year" | "years" => YEAR,
+        _ => return None,
+    };
+    Some(seconds)
+}
+
+fn named(word: &str) -> Option<i64> {
+    let seconds = match word {
+        "daily" | "day" => DAY,
+        "weekly" | "week" => WEEK,
+        "biweekly" | "fortnight" => 2.0 * WEEK,
+        "monthly" | "month" => MONTH,
+        "bimonthly" => 2.0 * MONTH,
+        "quarterly" | "quarter" => QUARTER,
+        "semiannual" => 6.0 * MONTH,
+        "annual" | "yearly" | "year" => YEAR,
+        "biannual" | "biyearly" => 2.0 * YEAR,
+        _ => return None,
+    };
+    Some(seconds as i64)
+}
+
+fn iso8601_seconds(dur: iso8601::Duration) -> i64 {
+    match dur {
+        iso8601::Duration::YMDHMS {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            millisecond,
+        } => {
+            let seconds = year as f64 * YEAR
+                + month as f64 * MONTH
+                + day as f64 * DAY
+                + hour as f64 * HOUR
+                + minute as f64 * MINUTE
+                + second as f64
+                + millisecond as f64 / 1000.0;
+            seconds.round() as i64
+        }
+        iso8601::Duration::Weeks(weeks) => weeks as i64 * WEEK as i64,
+    }
+}
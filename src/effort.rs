@@ -0,0 +1,208 @@
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Result, WrapErr};
+use cronwave::correction;
+use cronwave::dates::parse_datetime_in;
+use cronwave::duration::parse_duration;
+use cronwave::structs::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::config;
+use crate::ical;
+
+const EFFORT_FILE: &str = "effort-log.json";
+
+#[derive(Debug, Deserialize)]
+struct Interval {
+    start: String,
+    end: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+// completed tasks with an estimate, limited to the profile's `filter`
+fn fetch_done_tasks(filter: &str) -> Result<Vec<DoneTask>> {
+    ical::export(&ical::filtered(
+        filter,
+        &["status:completed", "estimated.any:"],
+    ))
+    .wrap_err("failed to fetch completed tasks")
+}
+
+// None when timewarrior is not installed
+fn fetch_intervals() -> Option<Vec<Interval>> {
+    let output = Command::new("timew").arg("export").output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+// the timewarrior hook tags intervals with the task description, some setups add the uuid too
+fn timewarrior_effort(task: &DoneTask, intervals: &[Interval]) -> i64 {
+    intervals
+        .iter()
+        .filter(|i| {
+            i.tags
+                .iter()
+                .any(|t| *t == task.description || *t == task.uuid)
+        })
+        .filter_map(|i| {
+            let start = parse_datetime_in(&i.start, &Utc).ok()?;
+            let end = parse_datetime_in(i.end.as_deref()?, &Utc).ok()?;
+            Some(end - start)
+        })
+        .sum()
+}
+
+// falls back to the "Started task"/"Stopped task" annotations from journal.time=on
+fn journal_effort(task: &DoneTask) -> i64 {
+    let mut entries: Vec<(i64, &str)> = task
+        .annotations
+        .iter()
+        .filter_map(|a| {
+            Some((
+                parse_datetime_in(&a.entry, &Utc).ok()?,
+                a.description.as_str(),
+            ))
+        })
+        .collect();
+    entries.sort_by_key(|(entry, _)| *entry);
+    let mut total = 0;
+    let mut started = None;
+    for (entry, description) in entries {
+        match description {
+            "Started task" => started = Some(entry),
+            "Stopped task" => {
+                if let Some(start) = started.take() {
+                    total += entry - start;
+                }
+            }
+            _ => (),
+        }
+    }
+    if let (Some(start), Some(end)) = (started, task.end.as_deref()) {
+        total += parse_datetime_in(end, &Utc)
+            .map(|end| end - start)
+            .unwrap_or(0);
+    }
+    total
+}
+
+fn log_path(profile: Option<&str>) -> Result<PathBuf> {
+    config::state_file(EFFORT_FILE, profile).map_err(|e| eyre!("no place for the effort log: {e}"))
+}
+
+fn load_records(profile: Option<&str>) -> Result<Vec<EffortRecord>> {
+    let path = log_path(profile)?;
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .wrap_err_with(|| format!("the effort log {} is corrupt", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+        Err(e) => Err(e).wrap_err_with(|| format!("failed to read {}", path.display())),
+    }
+}
+
+fn save_records(records: &[EffortRecord], profile: Option<&str>) -> Result<()> {
+    let path = log_path(profile)?;
+    std::fs::write(&path, serde_json::to_string_pretty(records)?)
+        .wrap_err_with(|| format!("failed to write {}", path.display()))
+}
+
+/// Records how long each completed task actually took and prints how far off
+/// the estimates were, per project and per tag.
+pub fn log(config_data: &ConfigInfo) -> Result<()> {
+    let profile = config_data.profile.as_deref();
+    let intervals = fetch_intervals();
+    let mut records = load_records(profile)?;
+    let mut added = 0;
+    for task in fetch_done_tasks(&config_data.planning.filter)? {
+        let Some(estimated) = task
+            .estimated
+            .as_deref()
+            .and_then(|e| parse_duration(e).ok())
+        else {
+            continue;
+        };
+        // a task timewarrior never saw may still have start/stop annotations
+        let actual = match &intervals {
+            Some(intervals) => match timewarrior_effort(&task, intervals) {
+                0 => journal_effort(&task),
+                tracked => tracked,
+            },
+            None => journal_effort(&task),
+        };
+        if actual <= 0 || estimated <= 0 {
+            continue;
+        }
+        let record = EffortRecord {
+            uuid: task.uuid.clone(),
+            description: task.description.clone(),
+            project: task.project.clone(),
+            tags: task.tags.clone(),
+            estimated,
+            actual,
+            completed: task
+                .end
+                .as_deref()
+                .and_then(|end| parse_datetime_in(end, &Utc).ok())
+                .unwrap_or(0),
+        };
+        match records.iter_mut().find(|r| r.uuid == record.uuid) {
+            Some(existing) => *existing = record,
+            None => {
+                records.push(record);
+                added += 1;
+            }
+        }
+    }
+    save_records(&records, profile)?;
+    println!(
+        "logged {added} new tasks ({} total) from {}",
+        records.len(),
+        if intervals.is_some() {
+            "timewarrior and taskwarrior start/stop"
+        } else {
+            "taskwarrior start/stop"
+        }
+    );
+    report(&records);
+    Ok(())
+}
+
+fn report(records: &[EffortRecord]) {
+    let mut projects: BTreeMap<String, Vec<&EffortRecord>> = BTreeMap::new();
+    let mut tags: BTreeMap<String, Vec<&EffortRecord>> = BTreeMap::new();
+    for record in records {
+        let project = record.project.clone().unwrap_or("(none)".to_string());
+        projects.entry(project).or_default().push(record);
+        for tag in &record.tags {
+            tags.entry(format!("+{tag}")).or_default().push(record);
+        }
+    }
+    println!(
+        "{:<24} {:>5} {:>10} {:>10} {:>7}",
+        "group", "tasks", "estimated", "actual", "ratio"
+    );
+    for (name, group) in projects.iter().chain(tags.iter()) {
+        let estimated: i64 = group.iter().map(|r| r.estimated).sum();
+        let actual: i64 = group.iter().map(|r| r.actual).sum();
+        println!(
+            "{:<24} {:>5} {:>9.1}h {:>9.1}h {:>7.2}",
+            name,
+            group.len(),
+            estimated as f64 / 3600.0,
+            actual as f64 / 3600.0,
+            actual as f64 / estimated as f64
+        );
+    }
+}
+
+/// Scales each task's estimate by how long finished tasks in the same
+/// project and with the same tags actually took, see `correction::factor`.
+pub fn apply_correction(tasks: &mut [Task], profile: Option<&str>) -> Result<()> {
+    correction::correct(tasks, &load_records(profile)?);
+    Ok(())
+}
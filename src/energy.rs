@@ -0,0 +1,103 @@
+use chrono::TimeZone;
+use std::collections::HashMap;
+
+use crate::dates::Tz;
+use crate::preferences::{parse_windows, Window};
+use crate::structs::{Energy, Task};
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Low,
+    Medium,
+    High,
+}
+
+impl Level {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        match input.trim().to_lowercase().as_str() {
+            "low" | "l" => Ok(Level::Low),
+            "medium" | "m" => Ok(Level::Medium),
+            "high" | "h" => Ok(Level::High),
+            other => Err(format!(
+                "unknown energy level '{other}', expected high, medium or low"
+            )),
+        }
+    }
+}
+
+/// The energy curve across the day and how much heavy work may be stacked.
+/// Anything outside the `high` and `low` windows counts as medium.
+#[derive(Debug, Clone)]
+pub struct Curve {
+    high: Vec<Window>,
+    low: Vec<Window>,
+    tags: HashMap<String, Level>,
+    max_heavy: i64,
+    // the zone the windows are read in
+    zone: Tz,
+}
+
+impl Curve {
+    pub fn new(config: &Energy, zone: Tz) -> Result<Self> {
+        Ok(Self {
+            high: parse_windows(&config.high)?,
+            low: parse_windows(&config.low)?,
+            tags: config
+                .tags
+                .iter()
+                .map(|(tag, level)| {
+                    let level = Level::parse(level)
+                        .map_err(|e| Error::Config(format!("energy level for tag {tag}: {e}")))?;
+                    Ok((tag.clone(), level))
+                })
+                .collect::<Result<_>>()?,
+            max_heavy: config.max_heavy_minutes * 60,
+            zone,
+        })
+    }
+
+    /// Whether back to back heavy work is capped.
+    pub fn limits_heavy(&self) -> bool {
+        self.max_heavy > 0
+    }
+
+    pub fn level_at(&self, timestamp: i64) -> Level {
+        let time = self.zone.timestamp_opt(timestamp, 0).unwrap();
+        if self.high.iter().any(|w| w.contains(&time)) {
+            Level::High
+        } else if self.low.iter().any(|w| w.contains(&time)) {
+            Level::Low
+        } else {
+            Level::Medium
+        }
+    }
+
+    /// The task's `energy` UDA, else the level of its first mapped tag, else
+    /// medium. Tasks are checked for a valid UDA when they are read.
+    pub fn level_of(&self, task: &Task) -> Level {
+        match task.energy.as_deref().map(Level::parse) {
+            Some(Ok(level)) => level,
+            _ => task
+                .tags
+                .iter()
+                .find_map(|t| self.tags.get(t).copied())
+                .unwrap_or(Level::Medium),
+        }
+    }
+
+    /// How badly the task fits at `start`, lower is better. `heavy_run` is how
+    /// long high energy work has been running back to back up to `start`.
+    pub fn score(&self, task: Level, start: i64, heavy_run: i64) -> i64 {
+        let mut score = 0;
+        if !self.high.is_empty() || !self.low.is_empty() {
+            // asking more than the period gives is worse than wasting a good period
+            let need = task as i64 - self.level_at(start) as i64;
+            score += if need > 0 { 2 * need } else { -need };
+        }
+        if task == Level::High && self.max_heavy > 0 && heavy_run >= self.max_heavy {
+            score += 3;
+        }
+        score
+    }
+}
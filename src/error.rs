@@ -0,0 +1,53 @@
+use std::fmt;
+
+/// Everything that can go wrong between reading tasks and writing the calendar.
+#[derive(Debug)]
+pub enum Error {
+    /// A config value cronwave cannot use.
+    Config(String),
+    /// Running `task` failed or its output made no sense.
+    Taskwarrior(String),
+    /// The CalDAV server could not be reached or refused the request.
+    CalDav(String),
+    /// A calendar could not be parsed.
+    Ical(String),
+    /// A task has nowhere its constraints allow it to go.
+    Infeasible(String),
+    Io(std::io::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(msg) => write!(f, "config: {msg}"),
+            Error::Taskwarrior(msg) => write!(f, "taskwarrior: {msg}"),
+            Error::CalDav(msg) => write!(f, "caldav: {msg}"),
+            Error::Ical(msg) => write!(f, "ical: {msg}"),
+            Error::Infeasible(msg) => write!(f, "cannot schedule: {msg}"),
+            Error::Io(e) => write!(f, "io: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::CalDav(e.to_string())
+    }
+}
@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::OpenOptions;
+use std::io::{BufRead, Read, Write};
+use std::process::{Command, Stdio};
+
+use crate::config;
+use cronwave::watch::{self, Decision};
+
+// one per profile; they all read the same taskwarrior, so a change is queued for each
+const QUEUE_FILE: &str = "reschedule-queue.jsonl";
+
+#[derive(Serialize, Deserialize, Debug)]
+struct QueueEntry {
+    uuid: String,
+    reason: String,
+    queued_at: i64,
+}
+
+impl QueueEntry {
+    // a new task wants `schedule`, a change to a placed one `reschedule`
+    fn added(&self) -> bool {
+        self.reason == watch::ADDED
+    }
+}
+
+// taskwarrior on-add hook: one task json line in, the same task (possibly modified) out
+pub fn on_add() -> i32 {
+    let mut lines = std::io::stdin().lock().lines();
+    let added = match lines.next() {
+        Some(Ok(line)) => line,
+        _ => return reject("cronwave: on-add hook got no task from taskwarrior"),
+    };
+    let task: Value = match serde_json::from_str(&added) {
+        Ok(task) => task,
+        Err(e) => return reject(&format!("cronwave: could not parse task json: {e}")),
+    };
+    match watch::decide(None, &task) {
+        Ok(decision) => apply(task, decision),
+        Err(msg) => reject(&msg),
+    }
+}
+
+// taskwarrior on-modify hook: original and modified task lines in, the modified task out
+pub fn on_modify() -> i32 {
+    let mut lines = std::io::stdin().lock().lines();
+    let (original, modified) = match (lines.next(), lines.next()) {
+        (Some(Ok(original)), Some(Ok(modified))) => (original, modified),
+        _ => return reject("cronwave: on-modify hook expected two task lines"),
+    };
+    let (original, modified): (Value, Value) = match (
+        serde_json::from_str(&original),
+        serde_json::from_str(&modified),
+    ) {
+        (Ok(original), Ok(modified)) => (original, modified),
+        _ => return reject("cronwave: could not parse task json"),
+    };
+    match watch::decide(Some(&original), &modified) {
+        Ok(decision) => apply(modified, decision),
+        Err(msg) => reject(&msg),
+    }
+}
+
+// taskwarrior on-exit hook: runs once the changes are saved, so a schedule started
+// from here reads them. Profiles with `hook.trigger` run whatever their queue holds
+pub fn on_exit() -> i32 {
+    // taskwarrior passes the changed tasks, which the queue already has
+    let _ = std::io::stdin().read_to_end(&mut vec![]);
+    let profiles = config::profiles().unwrap_or_else(|_| vec![None]);
+    for profile in profiles.iter().map(Option::as_deref) {
+        let trigger = config::get_config(profile, &[])
+            .map(|cfg| cfg.hook.trigger)
+            .unwrap_or(false);
+        if trigger && !queued_commands(profile).is_empty() {
+            trigger_queue(profile);
+        }
+    }
+    0
+}
+
+fn apply(mut task: Value, decision: Decision) -> i32 {
+    if decision.tag_unscheduled {
+        add_tag(&mut task, "unscheduled");
+    }
+    if let (Some(reason), Some(uuid)) = (&decision.queue, task["uuid"].as_str()) {
+        queue(uuid, reason);
+    }
+    accept(&task, decision.feedback)
+}
+
+fn add_tag(task: &mut Value, tag: &str) {
+    match task["tags"].as_array_mut() {
+        Some(tags) => tags.push(Value::from(tag)),
+        None => task["tags"] = Value::from(vec![tag]),
+    }
+}
+
+fn accept(task: &Value, feedback: Option<String>) -> i32 {
+    println!("{task}");
+    if let Some(feedback) = feedback {
+        println!("{feedback}");
+    }
+    0
+}
+
+fn reject(msg: &str) -> i32 {
+    println!("{msg}");
+    1
+}
+
+fn queue(uuid: &str, reason: &str) {
+    let entry = QueueEntry {
+        uuid: uuid.to_string(),
+        reason: reason.to_string(),
+        queued_at: chrono::Utc::now().timestamp(),
+    };
+    let profiles = config::profiles().unwrap_or_else(|_| vec![None]);
+    for profile in profiles.iter().map(Option::as_deref) {
+        let written = config::state_file(QUEUE_FILE, profile).and_then(|path| {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+            Ok(())
+        });
+        if let Err(e) = written {
+            eprintln!("cronwave: failed to queue reschedule: {e}");
+        }
+    }
+}
+
+// the entries of `profile`'s queue that parse; a missing queue is an empty one
+fn read_queue(profile: Option<&str>) -> Vec<QueueEntry> {
+    config::state_file(QUEUE_FILE, profile)
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .map(|contents| {
+            contents
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The commands that work through `profile`'s queue, reschedule first so
+/// new tasks are placed around the blocks that already moved.
+pub fn queued_commands(profile: Option<&str>) -> Vec<&'static str> {
+    let entries = read_queue(profile);
+    let mut commands = vec![];
+    if entries.iter().any(|e| !e.added()) {
+        commands.push("reschedule");
+    }
+    if entries.iter().any(QueueEntry::added) {
+        commands.push("schedule");
+    }
+    commands
+}
+
+// runs in the background so taskwarrior is not blocked on the caldav round trip
+fn trigger_queue(profile: Option<&str>) {
+    let spawned = std::env::current_exe().and_then(|exe| {
+        let mut child = Command::new(exe);
+        child.arg("queue");
+        if let Some(profile) = profile {
+            child.args(["--profile", profile]);
+        }
+        child
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    });
+    if let Err(e) = spawned {
+        eprintln!("cronwave: failed to start queue: {e}");
+    }
+}
+
+// called after a successful schedule (`added`) or reschedule of `profile` to drop the
+// entries of its own kind: entries queued from `started` on came in after the tasks
+// were read and stay. The calendar is already written by then, so a failure here is
+// only reported
+pub fn clear_queue(profile: Option<&str>, started: i64, added: bool) {
+    let cleared = config::state_file(QUEUE_FILE, profile).and_then(|path| {
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        // a line that does not parse is kept, it is not ours to drop
+        let pending: Vec<&str> = contents
+            .lines()
+            .filter(|line| {
+                serde_json::from_str::<QueueEntry>(line)
+                    .map_or(true, |e| e.added() != added || e.queued_at >= started)
+            })
+            .collect();
+        if pending.is_empty() {
+            std::fs::remove_file(path)?;
+        } else {
+            std::fs::write(path, pending.join("\n") + "\n")?;
+        }
+        Ok(())
+    });
+    if let Err(e) = cleared {
+        eprintln!("cronwave: failed to clear the reschedule queue: {e}");
+    }
+}
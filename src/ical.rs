@@ -1,271 +1,303 @@
-use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono::Utc;
+use cronwave::calendar::{parse_event, write_calendar};
+use cronwave::dates::parse_datetime_in;
+use cronwave::duration::parse_duration;
+use cronwave::energy::Level;
+use cronwave::store::{CalendarStore, TaskStore};
 use cronwave::structs::*;
-use ical::property::Property;
-use icalendar::{Calendar, CalendarComponent, CalendarDateTime, Component, DatePerhapsTime};
-use iso8601_duration::Duration;
-use rrule::RRule;
-use rrule::RRuleSet;
+use cronwave::{Error, Result};
+use icalendar::{Calendar, CalendarComponent, Component};
+use reqwest::blocking::Client;
+use reqwest::header::*;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
 use std::fs::read_to_string;
-use std::fs::File;
-use std::io::BufWriter;
-use std::io::Write;
 use std::process::Command;
-use std::str::FromStr;
-use tokio::task_local;
 
-fn week_and_day(week: u32, day: u32, year: i32) -> i64 {
-    let start = Local::with_ymd_and_hms(&Local, year, 0, 0, 0, 0, 0)
-        .unwrap()
-        .timestamp();
-    let num_days = week * 7 + day;
-    let num_day_i = num_days as i64;
-    num_day_i * 864000 + start
-}
-fn ordinal(year: i32, ddd: u32) -> i64 {
-    let start = Local::with_ymd_and_hms(&Local, year, 0, 0, 0, 0, 0)
-        .unwrap()
-        .timestamp();
-    let num_days = ddd as i64;
-    num_days * 864000 + start
-}
-fn convert_iso8601_to_timestamp(dur: iso8601::DateTime) -> i64 {
-    match dur.date {
-        iso8601::Date::YMD { year, month, day } => Local::with_ymd_and_hms(
-            &Local,
-            year,
-            month,
-            day,
-            dur.time.hour,
-            dur.time.minute,
-            dur.time.second,
+use crate::cache;
+
+// a task's fields that cronwave can't use, reported against the task
+fn task_field<T>(task: &RawTask, field: &str, value: Result<T, String>) -> Result<T, String> {
+    value.map_err(|e| {
+        format!(
+            "task {} '{}' has an invalid {field}: {e}",
+            task.id, task.description
         )
-        .unwrap()
-        .timestamp(),
-        iso8601::Date::Week {
-            year: year,
-            ww: ww,
-            d: d,
-        } => week_and_day(ww, d, year),
-        iso8601::Date::Ordinal { year: y, ddd: ddd } => ordinal(y, ddd),
+    })
+}
+
+// only set when at least one of the optimistic/likely/pessimistic UDAs is present
+fn parse_three_point(task: &RawTask, estimated: i64) -> Result<Option<ThreePoint>, String> {
+    if task.optimistic.is_none() && task.likely.is_none() && task.pessimistic.is_none() {
+        return Ok(None);
     }
+    let parse = |uda: &Option<String>, fallback: i64| match uda.as_deref() {
+        Some(value) => task_field(task, "three point estimate", parse_duration(value)),
+        None => Ok(fallback),
+    };
+    let likely = parse(&task.likely, estimated)?;
+    Ok(Some(ThreePoint {
+        optimistic: parse(&task.optimistic, likely.min(estimated))?,
+        likely,
+        pessimistic: parse(&task.pessimistic, likely.max(estimated))?,
+    }))
 }
-fn iso8601_dur_to_timestamp(dur: iso8601::Duration) -> i64 {
-    match dur {
-        iso8601::Duration::YMDHMS {
-            year,
-            month,
-            day,
-            hour,
-            minute,
-            second,
-            millisecond,
-        } => {
-            (year * 31536000 + month * 2629746 + day * 86400 + hour * 3600 + minute * 60 + second)
-                as i64
-        }
 
-        iso8601::Duration::Weeks(week) => week_and_day(week, 0, 0),
+pub fn parse_task(task: RawTask) -> Result<Task, String> {
+    let estimated = task_field(&task, "estimated value", parse_duration(&task.estimated))?;
+    // taskwarrior exports its dates in UTC
+    let due = task_field(&task, "due date", parse_datetime_in(&task.due, &Utc))?;
+    let start = match &task.start {
+        Some(start) => Some(task_field(
+            &task,
+            "start date",
+            parse_datetime_in(start, &Utc),
+        )?),
+        None => None,
+    };
+    if let Some(energy) = &task.energy {
+        task_field(&task, "energy", Level::parse(energy))?;
     }
+    Ok(Task {
+        estimated,
+        three_point: parse_three_point(&task, estimated)?,
+        energy: task.energy,
+        due,
+        start,
+        id: task.id,
+        uuid: task.uuid,
+        description: task.description,
+        status: task.status,
+        urgency: task.urgency,
+        project: task.project,
+        tags: task.tags,
+    })
 }
 
-pub fn fetch_tasks() -> Vec<Task> {
+pub fn export<T: DeserializeOwned>(filter: &[&str]) -> Result<Vec<T>> {
     let task_command = Command::new("task")
-        .args(["status:pending", "+unscheduled", "export"])
+        .args(filter)
+        .arg("export")
         .output()
-        .expect("failed to run task export");
-    let output_raw: Vec<RawTask> =
-        serde_json::from_slice(&task_command.stdout).expect("invalid taskwarrior output");
-
-    let mut output = vec![];
-    for task in output_raw {
-        let task_item = Task {
-            id: task.id,
-            uuid: task.uuid,
-            description: task.description,
-            due: convert_iso8601_to_timestamp(iso8601::DateTime::from_str(&task.due).unwrap()),
-            estimated: iso8601_dur_to_timestamp(
-                iso8601::Duration::from_str(&task.estimated).unwrap(),
-            ),
-            status: task.status,
-            urgency: task.urgency,
-            start: if task.start.is_some() {
-                let naive =
-                    NaiveDateTime::parse_from_str(task.start.unwrap().as_str(), "%Y%m%dT%H%M%SZ")
-                        .unwrap();
-                Some(
-                    Local::from_local_datetime(&Local, &naive)
-                        .unwrap()
-                        .timestamp(),
-                )
-            } else {
-                None
-            },
-        };
-
-        output.push(task_item);
+        .map_err(|e| Error::Taskwarrior(format!("failed to run task export: {e}")))?;
+    if !task_command.status.success() {
+        return Err(Error::Taskwarrior(format!(
+            "task export exited with {}: {}",
+            task_command.status,
+            String::from_utf8_lossy(&task_command.stderr).trim()
+        )));
     }
-    output
+    serde_json::from_slice(&task_command.stdout)
+        .map_err(|e| Error::Taskwarrior(format!("invalid task export output: {e}")))
 }
 
-pub fn fetch_tasks_scheduled() -> Vec<Task> {
-    let task_command = Command::new("task")
-        .args(["status:pending", "+scheduled", "export"])
-        .output()
-        .expect("failed to run task export");
+// a task that can't be parsed is left out of the plan instead of stopping it
+fn parse_tasks(raw: Vec<RawTask>) -> Vec<Task> {
+    raw.into_iter()
+        .filter_map(|task| {
+            parse_task(task)
+                .map_err(|e| eprintln!("warning: skipping {e}"))
+                .ok()
+        })
+        .collect()
+}
 
-    let output_raw: Vec<RawTask> =
-        serde_json::from_slice(&task_command.stdout).expect("invalid taskwarrior output");
-    let mut output = vec![];
-    for task in output_raw {
-        let task_item = Task {
-            id: task.id,
-            uuid: task.uuid,
-            description: task.description,
-            due: convert_iso8601_to_timestamp(iso8601::DateTime::from_str(&task.due).unwrap()),
-            estimated: iso8601_dur_to_timestamp(
-                iso8601::Duration::from_str(&task.estimated).unwrap(),
-            ),
-            status: task.status,
-            urgency: task.urgency,
-            start: if task.start.is_some() {
-                let naive =
-                    NaiveDateTime::parse_from_str(task.start.unwrap().as_str(), "%Y%m%dT%H%M%SZ")
-                        .unwrap();
-                Some(
-                    Local::from_local_datetime(&Local, &naive)
-                        .unwrap()
-                        .timestamp(),
-                )
-            } else {
-                None
-            },
-        };
+// the profile's filter in front of `base`, one argument per word
+pub fn filtered<'a>(filter: &'a str, base: &[&'a str]) -> Vec<&'a str> {
+    filter
+        .split_whitespace()
+        .chain(base.iter().copied())
+        .collect()
+}
 
-        output.push(task_item);
-    }
+pub fn fetch_tasks(filter: &str) -> Result<Vec<Task>> {
+    Ok(parse_tasks(export(&filtered(
+        filter,
+        &["status:pending", "+unscheduled"],
+    ))?))
+}
 
-    output.sort_by(|a, b| a.due.cmp(&b.due));
+pub fn fetch_tasks_scheduled(filter: &str) -> Result<Vec<Task>> {
+    let mut output = parse_tasks(export(&filtered(
+        filter,
+        &["status:pending", "+scheduled"],
+    ))?);
+    output.sort_by_key(|a| a.due);
+    Ok(output)
+}
 
-    output
+/// The state of every task, keyed by uuid. No filter, so completed and
+/// deleted tasks are in it too.
+pub fn fetch_task_states() -> Result<HashMap<String, TaskState>> {
+    let states: Vec<TaskState> = export(&[])?;
+    Ok(states
+        .into_iter()
+        .map(|state| (state.uuid.clone(), state))
+        .collect())
 }
-pub fn fetch_ical_text(config_data: ConfigInfo) {
+
+/// Downloads the calendar into the cache. When the cached copy has an ETag the
+/// server is asked whether it changed, and an unchanged calendar is not sent again.
+pub fn fetch_ical_text(config_data: &ConfigInfo) -> Result<()> {
+    let url = &config_data.caldav.cal_url;
+    let cached = cache::entry(config_data, url);
     let client = reqwest::blocking::Client::new();
-    let response = client
-        .get(config_data.Basic.cal_url)
-        .basic_auth(
-            config_data.Basic.cal_username,
-            Some(config_data.Basic.cal_pass),
-        )
-        .send()
-        .expect("failed to fetch ical");
-    let school = File::create("school.ics").unwrap();
-    let mut writer = BufWriter::new(school);
-    // println!("Writen the ics{:?}", response.text());
-    writer
-        .write_all(response.text().unwrap().as_bytes())
-        .expect("failed to write ics");
+    let mut request = client.get(url).basic_auth(
+        &config_data.caldav.cal_username,
+        Some(config_data.caldav.cal_pass.expose()),
+    );
+    if let Some(etag) = cached.as_ref().and_then(|c| c.etag.as_ref()) {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    let response = request.send()?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        if let Some(cached) = cached {
+            return cache::touch(config_data, cached);
+        }
+    }
+    if !response.status().is_success() {
+        return Err(Error::CalDav(format!(
+            "fetching {url} returned {}",
+            response.status()
+        )));
+    }
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|etag| etag.to_str().ok())
+        .map(str::to_string);
+    cache::store(config_data, url, etag, &response.text()?)
 }
-pub fn parse_ical_blocks() -> Vec<TimeBlock> {
-    let contents = read_to_string("school.ics").expect("couldnt read school.ics");
-    let parsed_calendar: Calendar = contents.parse().unwrap();
+
+/// The blocks in the cached calendar, and a note for every event that could
+/// not be read.
+pub fn parse_ical_blocks(config: &ConfigInfo) -> Result<(Vec<TimeBlock>, Vec<String>)> {
+    let path = cache::calendar_path(config, &config.caldav.cal_url)?;
+    let contents = read_to_string(&path)
+        .map_err(|e| Error::Ical(format!("couldn't read {}: {e}", path.display())))?;
+    let parsed_calendar: Calendar = contents.parse().map_err(Error::Ical)?;
     let mut timebloc_vec = vec![];
+    let mut skipped = vec![];
     for component in &parsed_calendar.components {
         if let CalendarComponent::Event(event) = component {
-            let dtstart = match event.get_start() {
-                Some(dt) => match dt {
-                    DatePerhapsTime::Date(date) => Local
-                        .from_local_datetime(
-                            &date.and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap()),
-                        )
-                        .unwrap()
-                        .timestamp(),
-                    DatePerhapsTime::DateTime(dt) => match dt {
-                        CalendarDateTime::Floating(float) => {
-                            Local::from_local_datetime(&Local, &float)
-                                .unwrap()
-                                .timestamp()
-                        }
-                        CalendarDateTime::Utc(utc) => {
-                            Local::from_utc_datetime(&Local, &utc.naive_utc()).timestamp()
-                        }
-                        CalendarDateTime::WithTimezone { date_time, tzid } => {
-                            Local::from_local_datetime(&Local, &date_time)
-                                .unwrap()
-                                .timestamp()
-                        }
-                    },
-                },
-                None => panic!("no event start"),
-            };
-            let duration = match event.properties().get("DURATION") {
-                Some(dur) => {
-                    let float = dur
-                        .value()
-                        .parse::<Duration>()
-                        .unwrap()
-                        .num_seconds()
-                        .unwrap();
-                    Some(float as i64)
-                }
-                None => None,
-            };
-            let uid = event.get_uid().expect("no uuid");
-            let summary = event.get_summary().expect("no summary");
-            let dtstamp = event.get_timestamp().unwrap();
-            let rrule = match event.properties().get("RRULE") {
-                Some(rrule) => {
-                    let rrule =
-                        rrule::RRule::from_str(rrule.value()).expect("failed to parse to rrule");
-                    let tz = rrule::Tz::Local(Local);
-                    let time = Utc::timestamp_opt(&Utc, dtstart, 0)
-                        .unwrap()
-                        .with_timezone(&tz);
-                    Some(rrule.validate(time).unwrap())
-                }
-                None => None,
-            };
-            let dtend = match event.get_end() {
-                Some(end) => match end {
-                    DatePerhapsTime::Date(date) => Some(
-                        Local
-                            .from_local_datetime(
-                                &date.and_time(NaiveTime::from_hms_opt(0, 0, 0).unwrap()),
-                            )
-                            .unwrap()
-                            .timestamp(),
-                    ),
-                    DatePerhapsTime::DateTime(dt) => match dt {
-                        CalendarDateTime::Floating(float) => Some(
-                            Local::from_local_datetime(&Local, &float)
-                                .unwrap()
-                                .timestamp(),
-                        ),
-                        CalendarDateTime::Utc(utc) => {
-                            Some(Local::from_utc_datetime(&Local, &utc.naive_utc()).timestamp())
-                        }
-                        CalendarDateTime::WithTimezone { date_time, tzid } => {
-                            let tz: chrono_tz::Tz = tzid.parse().unwrap();
-                            Some(
-                                Local::from_local_datetime(&Local, &date_time)
-                                    .unwrap()
-                                    .timestamp(),
-                            )
-                        }
-                    },
-                },
-                None => None,
-            };
-            timebloc_vec.push(TimeBlock {
-                dtstart,
-                dtend,
-                summary: summary.to_string(),
-                rrule,
-                uid: uid.to_string(),
-                duration,
-                dtstamp,
-            });
+            match parse_event(event, config) {
+                Ok(block) => timebloc_vec.push(block),
+                Err(e) => skipped.push(format!(
+                    "'{}' ({}): {e}",
+                    event.get_summary().unwrap_or_default(),
+                    event.get_uid().unwrap_or("no uid")
+                )),
+            }
         }
     }
     timebloc_vec.sort_by_key(|x| x.dtstart);
-    timebloc_vec
+    Ok((timebloc_vec, skipped))
+}
+
+fn mark_tasks_scheduled(uuids: &[String]) -> Result<()> {
+    for uuid in uuids {
+        let result = Command::new("task")
+            .args([uuid, "modify", "+scheduled", "-unscheduled"])
+            .output()
+            .map_err(|e| Error::Taskwarrior(format!("failed to run task modify: {e}")))?;
+        println!(
+            "task modify {uuid} +scheduled -unscheduled: {}",
+            result.status
+        );
+    }
+    Ok(())
+}
+
+fn create_caldav_events(config_data: &ConfigInfo, blocks: &[TimeBlock]) -> Result<()> {
+    let combined = write_calendar(blocks, config_data.planning.zone()?);
+
+    // Set up HTTP client and headers
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/calendar"));
+
+    let client = Client::new();
+
+    // Send the PUT request with Basic auth
+    let response = client
+        .put(&config_data.caldav.cal_url)
+        .basic_auth(
+            &config_data.caldav.cal_username,
+            Some(config_data.caldav.cal_pass.expose()),
+        )
+        .headers(headers)
+        .body(combined)
+        .send()?;
+
+    if response.status().is_success() {
+        println!("✅ Events created ");
+
+        Ok(())
+    } else {
+        Err(Error::CalDav(format!(
+            "❌ Failed to create event: {}",
+            response.status()
+        )))
+    }
+}
+
+/// Taskwarrior through the `task` command.
+pub struct Taskwarrior {
+    // the profile's filter, empty for every task
+    filter: String,
+}
+
+impl Taskwarrior {
+    pub fn new(config: &ConfigInfo) -> Self {
+        Self {
+            filter: config.planning.filter.clone(),
+        }
+    }
+}
+
+impl TaskStore for Taskwarrior {
+    fn unscheduled(&self) -> Result<Vec<Task>> {
+        fetch_tasks(&self.filter)
+    }
+
+    fn scheduled(&self) -> Result<Vec<Task>> {
+        fetch_tasks_scheduled(&self.filter)
+    }
+
+    fn mark_scheduled(&self, uuids: &[String]) -> Result<()> {
+        mark_tasks_scheduled(uuids)
+    }
+}
+
+/// The CalDAV calendar from the config, downloaded to the cache.
+pub struct CalDav {
+    config: ConfigInfo,
+}
+
+impl CalDav {
+    pub fn new(config: ConfigInfo) -> Self {
+        Self { config }
+    }
+}
+
+impl CalendarStore for CalDav {
+    fn load(&self) -> Result<Vec<TimeBlock>> {
+        fetch_ical_text(&self.config)?;
+        let (blocks, skipped) = parse_ical_blocks(&self.config)?;
+        for event in skipped {
+            eprintln!("warning: skipping event {event}");
+        }
+        Ok(blocks)
+    }
+
+    fn save(&self, blocks: &[TimeBlock]) -> Result<()> {
+        // the PUT replaces the whole calendar, so an event we could not read would be lost
+        let (_, skipped) = parse_ical_blocks(&self.config)?;
+        if !skipped.is_empty() {
+            return Err(Error::Ical(format!(
+                "not saving, the calendar would lose events cronwave can't read: {}",
+                skipped.join("; ")
+            )));
+        }
+        create_caldav_events(&self.config, blocks)
+    }
 }
@@ -0,0 +1,260 @@
+use color_eyre::eyre::{bail, Result, WrapErr};
+use cronwave::credentials;
+use cronwave::preferences::Window;
+use cronwave::schema;
+use cronwave::structs::{Account, ConfigInfo, Planning, Secret};
+use cronwave::Error;
+use inquire::validator::Validation;
+use inquire::{Confirm, CustomType, Select, Text};
+use regex::Regex;
+use reqwest::blocking::Client;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Method, StatusCode, Url};
+
+// init never writes the password itself into the config file
+const PASSWORD_SOURCES: [&str; 2] = [
+    "a command that prints it, such as `pass show caldav`",
+    "a file only you can read",
+];
+
+// the text of every <name> element, whatever namespace prefix the server uses
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let name = regex::escape(name);
+    let re = Regex::new(&format!(
+        r"(?s)<(?:[\w-]+:)?{name}(?:\s[^>]*)?>(.*?)</(?:[\w-]+:)?{name}>"
+    ))
+    .unwrap();
+    re.captures_iter(xml)
+        .filter_map(|c| c.get(1))
+        .map(|m| m.as_str())
+        .collect()
+}
+
+// the href inside the first <prop> element, like current-user-principal
+fn href_in(xml: &str, prop: &str) -> Option<String> {
+    let inner = elements(xml, prop).into_iter().next()?;
+    elements(inner, "href")
+        .into_iter()
+        .next()
+        .map(|href| href.trim().to_string())
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+pub fn propfind(
+    client: &Client,
+    url: &Url,
+    depth: &str,
+    props: &str,
+    basic: &Account,
+    password: &Secret,
+) -> cronwave::Result<String> {
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"><d:prop>{props}</d:prop></d:propfind>"#
+    );
+    let response = client
+        .request(Method::from_bytes(b"PROPFIND").unwrap(), url.clone())
+        .basic_auth(&basic.cal_username, Some(password.expose()))
+        .header("Depth", depth)
+        .header(CONTENT_TYPE, "application/xml")
+        .body(body)
+        .send()?;
+    match response.status() {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(Error::CalDav(
+            "the server rejected the username or password".to_string(),
+        )),
+        status if !status.is_success() => {
+            Err(Error::CalDav(format!("PROPFIND {url} returned {status}")))
+        }
+        _ => Ok(response.text()?),
+    }
+}
+
+/// Finds the calendars the user can see, following the principal and the
+/// calendar home from `url` as RFC 4791 describes. Returns (name, url) pairs.
+fn discover(url: &Url, basic: &Account, password: &Secret) -> cronwave::Result<Vec<(String, Url)>> {
+    let client = Client::new();
+    let join = |base: &Url, href: Option<String>| {
+        href.and_then(|href| base.join(&href).ok())
+            .unwrap_or_else(|| base.clone())
+    };
+    let root = propfind(
+        &client,
+        url,
+        "0",
+        "<d:current-user-principal/>",
+        basic,
+        password,
+    )?;
+    let principal = join(url, href_in(&root, "current-user-principal"));
+    let principal_xml = propfind(
+        &client,
+        &principal,
+        "0",
+        "<c:calendar-home-set/>",
+        basic,
+        password,
+    )?;
+    let home = join(&principal, href_in(&principal_xml, "calendar-home-set"));
+    let listing = propfind(
+        &client,
+        &home,
+        "1",
+        "<d:displayname/><d:resourcetype/>",
+        basic,
+        password,
+    )?;
+    let is_calendar = Regex::new(r"<(?:[\w-]+:)?calendar[\s/>]").unwrap();
+    let mut calendars = vec![];
+    for response in elements(&listing, "response") {
+        let resourcetype = elements(response, "resourcetype").join("");
+        if !is_calendar.is_match(&resourcetype) {
+            continue;
+        }
+        let Some(href) = elements(response, "href").into_iter().next() else {
+            continue;
+        };
+        let Ok(calendar) = home.join(href.trim()) else {
+            continue;
+        };
+        let name = elements(response, "displayname")
+            .into_iter()
+            .map(|name| unescape(name.trim()))
+            .find(|name| !name.is_empty())
+            .unwrap_or_else(|| calendar.path().to_string());
+        calendars.push((name, calendar));
+    }
+    Ok(calendars)
+}
+
+fn ask_credentials(url: &Url) -> Result<Account> {
+    let cal_username = Text::new("Username").prompt()?;
+    let mut basic = Account {
+        cal_url: url.to_string(),
+        cal_username,
+        cal_pass: Secret::default(),
+        password_command: None,
+        password_file: None,
+    };
+    let source = Select::new(
+        "Where should the password come from?",
+        PASSWORD_SOURCES.to_vec(),
+    )
+    .prompt()?;
+    if source == PASSWORD_SOURCES[0] {
+        basic.password_command = Some(Text::new("Password command").prompt()?);
+    } else {
+        basic.password_file = Some(
+            Text::new("Password file")
+                .with_help_message("readable only by you, chmod 600")
+                .prompt()?,
+        );
+    }
+    Ok(basic)
+}
+
+fn validate_windows(input: &str) -> Result<Validation, inquire::CustomUserError> {
+    for window in input.split(';').map(str::trim).filter(|w| !w.is_empty()) {
+        if let Err(e) = Window::parse(window) {
+            return Ok(Validation::Invalid(e.into()));
+        }
+    }
+    Ok(Validation::Valid)
+}
+
+/// Asks for the server, credentials, calendar, working hours and horizon,
+/// checks them against the server and writes the config of `profile`.
+pub fn init(profile: Option<&str>) -> Result<()> {
+    let path = confy::get_configuration_file_path("cronwave", profile)?;
+    if path.exists()
+        && !Confirm::new(&format!("{} exists, overwrite it?", path.display()))
+            .with_default(false)
+            .prompt()?
+    {
+        return Ok(());
+    }
+
+    let url = Text::new("CalDAV server URL")
+        .with_help_message("e.g. https://cal.example.com/dav/")
+        .with_validator(|input: &str| {
+            Ok(match Url::parse(input.trim()) {
+                Ok(url) if url.scheme() == "https" || url.scheme() == "http" => Validation::Valid,
+                _ => Validation::Invalid("expected an http(s) URL".into()),
+            })
+        })
+        .prompt()?;
+    let url = Url::parse(url.trim())?;
+
+    // ask again until the server accepts the credentials
+    let (mut basic, calendars) = loop {
+        let basic = ask_credentials(&url)?;
+        let attempt = credentials::resolve_password(&basic)
+            .and_then(|password| discover(&url, &basic, &password));
+        match attempt {
+            Ok(calendars) => break (basic, calendars),
+            Err(e) => {
+                println!("{e}");
+                if !Confirm::new("Try again?").with_default(true).prompt()? {
+                    bail!("no working credentials for {url}");
+                }
+            }
+        }
+    };
+    if calendars.is_empty() {
+        bail!("found no calendars at {url}");
+    }
+    let names: Vec<&str> = calendars.iter().map(|(name, _)| name.as_str()).collect();
+    let chosen = Select::new("Calendar to schedule into", names).prompt()?;
+    let (_, calendar) = calendars
+        .iter()
+        .find(|(name, _)| name == chosen)
+        .expect("the chosen calendar is one of the listed ones");
+    basic.cal_url = calendar.to_string();
+
+    let working_hours = Text::new("Working hours, separated by ';'")
+        .with_default("weekdays 09:00-17:00")
+        .with_help_message("leave empty to schedule at any time")
+        .with_validator(validate_windows)
+        .prompt()?;
+    let working_hours: Vec<String> = working_hours
+        .split(';')
+        .map(str::trim)
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect();
+    let defaults = ConfigInfo::default();
+    let days_ahead = CustomType::<i64>::new("Days ahead to plan")
+        .with_default(defaults.planning.days_ahead)
+        .with_validator(|days: &i64| {
+            Ok(if *days > 0 {
+                Validation::Valid
+            } else {
+                Validation::Invalid("must be at least one day".into())
+            })
+        })
+        .prompt()?;
+    let filter = Text::new("Taskwarrior filter for the tasks to schedule")
+        .with_help_message("such as project:work, leave empty for every task")
+        .prompt()?;
+
+    let config_data = ConfigInfo {
+        caldav: basic,
+        planning: Planning {
+            days_ahead,
+            working_hours,
+            filter: filter.trim().to_string(),
+            timezone: String::new(),
+        },
+        ..defaults
+    };
+    schema::validate(&config_data)?;
+    confy::store("cronwave", profile, &config_data).wrap_err("failed to write the config")?;
+    println!("wrote {}", path.display());
+    Ok(())
+}
@@ -1 +1,22 @@
+pub mod availability;
+pub mod batch;
+pub mod calendar;
+pub mod clock;
+pub mod correction;
+pub mod credentials;
+pub mod dates;
+pub mod duration;
+pub mod energy;
+mod error;
+pub mod plan;
+pub mod preferences;
+pub mod reconcile;
+pub mod risk;
+pub mod schema;
+pub mod solver;
+pub mod store;
 pub mod structs;
+pub mod travel;
+pub mod watch;
+
+pub use error::{Error, Result};
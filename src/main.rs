@@ -1,60 +1,308 @@
+mod cache;
 mod config;
+mod doctor;
+mod effort;
+mod hook;
 mod ical;
+mod init;
 mod schedule;
+mod simulate;
 mod ui;
 mod whentomeet;
 
-use inquire::Text;
-use std::any::Any;
+use inquire::{Password, Text};
 
 use clap::Parser;
 use clap_derive::Parser as Parser_derive;
+use color_eyre::eyre::{bail, OptionExt, Result, WrapErr};
+use cronwave::clock::{Clock, FixedClock, SystemClock};
+use cronwave::credentials;
+use cronwave::dates::parse_datetime_in;
+use cronwave::plan;
+use cronwave::reconcile;
+use cronwave::schema;
+use cronwave::store::{CalendarStore, TaskStore};
 use cronwave::structs::{ConfigInfo, Task, TimeBlock};
 
 #[derive(Parser_derive, Debug)]
 struct Args {
     argument: String,
-    second_arg: Option<usize>,
+    second_arg: Option<String>,
+    /// plan as if it were this time, e.g. 2025-09-01T09:00
+    #[arg(long)]
+    now: Option<String>,
+    /// with `cache`, delete the cached calendars
+    #[arg(long)]
+    clear: bool,
+    /// use this profile's config, or `all` to plan every profile in turn
+    #[arg(long)]
+    profile: Option<String>,
+    /// override a config value for this run, e.g. planning.days_ahead=30
+    #[arg(long = "set", value_name = "KEY=VALUE")]
+    set: Vec<String>,
+    /// with `config show`, print every effective value and where it came from
+    #[arg(long)]
+    resolved: bool,
+    /// with `simulate`, seed the random draws to repeat an earlier run
+    #[arg(long)]
+    seed: Option<u64>,
 }
 
-fn main() {
-    let args = Args::try_parse().unwrap();
+impl Args {
+    // the second argument as a task id or a count
+    fn number(&self) -> Result<Option<usize>> {
+        self.second_arg
+            .as_deref()
+            .map(|arg| {
+                arg.parse()
+                    .wrap_err_with(|| format!("expected a number, found '{arg}'"))
+            })
+            .transpose()
+    }
+}
+
+// the commands `--profile all` can run, once per profile
+const ALL_PROFILE_COMMANDS: [&str; 8] = [
+    "schedule",
+    "reschedule",
+    "queue",
+    "reconcile",
+    "simulate",
+    "cache",
+    "doctor",
+    "config",
+];
+
+fn profiles(args: &Args) -> Result<Vec<Option<String>>> {
+    match args.profile.as_deref() {
+        Some("all") => {
+            if !ALL_PROFILE_COMMANDS.contains(&args.argument.as_str()) {
+                bail!(
+                    "--profile all only works with {}",
+                    ALL_PROFILE_COMMANDS.join(", ")
+                );
+            }
+            Ok(config::profiles()?)
+        }
+        profile => Ok(vec![profile.map(str::to_string)]),
+    }
+}
 
-    let config_info = config::get_config();
-    let config_data = config_info.expect("failed to get config info");
-    let tasks = ical::fetch_tasks();
-    let tasks_scheduled = ical::fetch_tasks_scheduled();
-    // println!("\n, \n, TASKS, \n, {:?}", tasks);
-    ical::fetch_ical_text(config_data.clone());
-    let timeblock = ical::parse_ical_blocks();
+// the calendar of a profile, loaded as the profile's own commands would
+fn load_calendar(config_data: &mut ConfigInfo) -> Result<Vec<TimeBlock>> {
+    schema::validate(config_data)?;
+    config_data.caldav.cal_pass = credentials::resolve_password(&config_data.caldav)
+        .wrap_err("failed to get the CalDAV password")?;
+    ical::CalDav::new(config_data.clone())
+        .load()
+        .wrap_err("failed to load the calendar")
+}
+
+// `--now` is wall-clock time in the profile's zone
+fn clock(args: &Args, config_data: &ConfigInfo) -> Result<Box<dyn Clock>> {
+    Ok(match &args.now {
+        Some(now) => Box::new(FixedClock(
+            parse_datetime_in(now, &config_data.planning.zone()?)
+                .map_err(|e| color_eyre::eyre::eyre!(e))
+                .wrap_err("--now is not a valid date")?,
+        )),
+        None => Box::new(SystemClock),
+    })
+}
+
+fn main() -> Result<()> {
+    color_eyre::install()?;
+    let args = Args::parse();
+    // these don't need the calendar, and hooks must not touch the network or call `task`
     match args.argument.as_str() {
-        "schedule" => schedule::schedule(tasks, config_data, timeblock),
-        "reschedule" => schedule::reschedule(timeblock, tasks_scheduled, config_data),
+        "on-add" => std::process::exit(hook::on_add()),
+        "on-modify" => std::process::exit(hook::on_modify()),
+        "on-exit" => std::process::exit(hook::on_exit()),
+        "init" => return init::init(args.profile.as_deref()),
+        "doctor" => {
+            let profiles = profiles(&args)?;
+            let mut code = 0;
+            for profile in &profiles {
+                if profiles.len() > 1 {
+                    println!("profile {}", profile.as_deref().unwrap_or("default"));
+                }
+                code = code.max(doctor::doctor(profile.as_deref(), &args.set));
+            }
+            std::process::exit(code)
+        }
+        "config" => {
+            if args.second_arg.as_deref() != Some("show") {
+                bail!("expected `config show`");
+            }
+            for profile in profiles(&args)? {
+                config::show(profile.as_deref(), &args.set, args.resolved)
+                    .wrap_err("failed to get config info")?;
+            }
+            return Ok(());
+        }
+        "log" => {
+            // the effort log is per profile, `all` would be taken as a profile's name
+            if args.profile.as_deref() == Some("all") {
+                bail!("--profile all does not work with log, pass one profile at a time");
+            }
+            let config_data = config::get_config(args.profile.as_deref(), &args.set)
+                .wrap_err("failed to get config info")?;
+            return effort::log(&config_data).wrap_err("failed to log effort");
+        }
+        "lock" | "unlock" => {
+            let id = args.number()?.ok_or_eyre("expected task id to lock")?;
+            let tag = if args.argument == "lock" { "+" } else { "-" };
+            let result = std::process::Command::new("task")
+                .arg(id.to_string())
+                .arg("modify")
+                .arg(format!("{tag}{}", plan::PIN_TAG))
+                .status()
+                .wrap_err("failed to run task modify")?;
+            println!("result of task modify command: {result}");
+            return Ok(());
+        }
+        _ => (),
+    }
+
+    let profiles = profiles(&args)?;
+    for profile in &profiles {
+        if profiles.len() > 1 {
+            println!("profile {}", profile.as_deref().unwrap_or("default"));
+        }
+        // works through what the hooks queued for this profile
+        let commands = if args.argument == "queue" {
+            hook::queued_commands(profile.as_deref())
+        } else {
+            vec![args.argument.as_str()]
+        };
+        for command in commands {
+            let config_data = config::get_config(profile.as_deref(), &args.set)
+                .wrap_err("failed to get config info")?;
+            if command == "cache" {
+                if args.clear {
+                    cache::clear(&config_data).wrap_err("failed to clear the cache")?;
+                } else {
+                    cache::show(&config_data).wrap_err("failed to read the cache")?;
+                }
+                continue;
+            }
+            // loaded again for every profile, so the blocks placed just before count as busy
+            let mut others = vec![];
+            for other in profiles.iter().filter(|other| *other != profile) {
+                let mut other_config = config::get_config(other.as_deref(), &args.set)?;
+                others.extend(load_calendar(&mut other_config)?.into_iter().map(|block| {
+                    TimeBlock {
+                        foreign: true,
+                        ..block
+                    }
+                }));
+            }
+            let clock = clock(&args, &config_data)?;
+            run(&args, command, config_data, &others, clock.as_ref())?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `command` against one profile. `others` are the events of the other
+/// profiles, busy time that is never written to this profile's calendar.
+fn run(
+    args: &Args,
+    command: &str,
+    mut config_data: ConfigInfo,
+    others: &[TimeBlock],
+    clock: &dyn Clock,
+) -> Result<()> {
+    // hook entries queued from here on may be about changes this run does not see
+    let started = chrono::Utc::now().timestamp();
+    let taskwarrior = ical::Taskwarrior::new(&config_data);
+    let mut tasks = taskwarrior
+        .unscheduled()
+        .wrap_err("failed to fetch tasks")?;
+    if config_data.estimates.apply_correction {
+        effort::apply_correction(&mut tasks, config_data.profile.as_deref())
+            .wrap_err("failed to correct estimates")?;
+    }
+    let tasks_scheduled = taskwarrior
+        .scheduled()
+        .wrap_err("failed to fetch scheduled tasks")?;
+    let mut timeblock = load_calendar(&mut config_data)?;
+    match command {
+        "schedule" => {
+            plan::subtract_locked(&mut tasks, &timeblock);
+            schedule::schedule(tasks, config_data, timeblock, others, clock, started)?;
+        }
+        // reconcile first so blocks of finished tasks go back into the pool of free time
+        "reschedule" | "reconcile" => {
+            let states = ical::fetch_task_states().wrap_err("failed to reconcile the calendar")?;
+            let done = reconcile::reconcile(&mut timeblock, &states, clock.now());
+            println!(
+                "reconciled calendar: removed {} blocks, shortened {}, freed {} minutes",
+                done.removed,
+                done.shrunk,
+                done.freed / 60
+            );
+            schedule::reschedule(
+                timeblock,
+                tasks_scheduled,
+                config_data,
+                others,
+                clock,
+                started,
+            )?
+        }
         "done" => delete(
             config_data,
             tasks_scheduled,
-            args.second_arg.expect("expected task id to delete"),
+            args.number()?.ok_or_eyre("expected task id to delete")?,
             timeblock,
-        ),
+            clock,
+            started,
+        )?,
+        "simulate" => {
+            let mut all_tasks = tasks;
+            all_tasks.extend(tasks_scheduled);
+            let mut events = simulate::without_task_blocks(timeblock, &all_tasks);
+            events.extend(others.iter().cloned());
+            events.sort_by_key(|b| b.dtstart);
+            simulate::simulate(
+                all_tasks,
+                events,
+                args.number()?.unwrap_or(2000),
+                args.seed.unwrap_or_else(rand::random),
+                &config_data,
+                clock,
+            )
+            .wrap_err("simulation failed")?;
+        }
         "ui" => {
-            ui::ui(timeblock);
+            ui::ui(timeblock).wrap_err("ui failed")?;
         }
         "meet" => {
-            let url = Text::new("url of when2meet").prompt().unwrap();
-            let name = Text::new("name").prompt().unwrap();
-            let pass = Text::new("pass").prompt().unwrap();
+            let url = Text::new("url of when2meet").prompt()?;
+            let name = Text::new("name").prompt()?;
+            let pass = Password::new("pass").without_confirmation().prompt()?;
 
-            let rt = tokio::runtime::Runtime::new().unwrap();
-            rt.block_on(async { whentomeet::meet(url, name, pass, timeblock).await });
+            let rt = tokio::runtime::Runtime::new()?;
+            rt.block_on(async { whentomeet::meet(url, name, pass, timeblock).await })
+                .wrap_err("failed to fill out when2meet")?;
         }
         _ => (),
     }
+    Ok(())
 }
-fn delete(config_data: ConfigInfo, mut tasks: Vec<Task>, num: usize, mut blocks: Vec<TimeBlock>) {
+fn delete(
+    config_data: ConfigInfo,
+    mut tasks: Vec<Task>,
+    num: usize,
+    mut blocks: Vec<TimeBlock>,
+    clock: &dyn Clock,
+    started: i64,
+) -> Result<()> {
     let task = tasks
         .iter()
         .find(|x| x.id == num)
-        .expect("did not find the id number requested to delete");
+        .ok_or_eyre("did not find the id number requested to delete")?;
     let name = task.clone().description;
     let uuid_vec: Vec<TimeBlock> = blocks
         .clone()
@@ -64,21 +312,21 @@ fn delete(config_data: ConfigInfo, mut tasks: Vec<Task>, num: usize, mut blocks:
     let number = tasks
         .iter()
         .position(|x| x.id == num)
-        .expect("could not find id number requested to delete");
+        .ok_or_eyre("could not find id number requested to delete")?;
 
     for uid in uuid_vec {
         let blocks_num = blocks
             .iter()
             .position(|x| x.uid == uid.uid)
-            .expect("did not find the uuid in the calendar to delete");
+            .ok_or_eyre("did not find the uuid in the calendar to delete")?;
         blocks.remove(blocks_num);
     }
     let result = std::process::Command::new("task")
         .arg("done")
         .arg(num.to_string())
         .output()
-        .unwrap();
+        .wrap_err("failed to run task done")?;
     println!("result of task delete command :{:?}", result);
     tasks.remove(number);
-    schedule::reschedule(blocks, tasks, config_data);
+    schedule::reschedule(blocks, tasks, config_data, &[], clock, started)
 }
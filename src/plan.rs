@@ -0,0 +1,670 @@
+use chrono::{DateTime, TimeZone};
+use rrule::RRuleSet;
+use std::collections::HashMap;
+
+use crate::availability::free_between;
+use crate::batch;
+use crate::dates::Tz;
+use crate::energy::{Curve, Level};
+use crate::preferences::{self, Constraints};
+use crate::schema;
+use crate::solver::{self, SolverReport};
+use crate::structs::*;
+use crate::travel::Places;
+use crate::{Error, Result};
+
+/// A task that the plan finishes after its due date.
+#[derive(Debug, Clone)]
+pub struct Late {
+    pub uuid: String,
+    pub description: String,
+    pub due: i64,
+    pub finish: i64,
+}
+
+/// What `plan` decided.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    /// The blocks placed for the tasks.
+    pub placed: Vec<TimeBlock>,
+    /// The calendar blocks that are not over yet, with the placed ones added.
+    pub calendar: Vec<TimeBlock>,
+    pub late: Vec<Late>,
+    /// Set when the optimal solver ran.
+    pub solver: Option<SolverReport>,
+}
+
+impl Schedule {
+    /// The uuids of the tasks that got blocks, batch members included.
+    pub fn placed_tasks(&self) -> Vec<String> {
+        let mut uuids: Vec<String> = self
+            .placed
+            .iter()
+            .flat_map(|b| {
+                if b.batch.is_empty() {
+                    b.task_uuid.iter().cloned().collect()
+                } else {
+                    b.batch.clone()
+                }
+            })
+            .collect();
+        uuids.sort();
+        uuids.dedup();
+        uuids
+    }
+}
+
+pub fn block_end(block: &TimeBlock) -> i64 {
+    match block.duration {
+        Some(dur) => block.dtstart + dur,
+        None => block.dtend.unwrap_or(block.dtstart),
+    }
+}
+
+// drops blocks that are already over and returns the free time around the rest
+pub fn free_gaps(blocks: &mut Vec<TimeBlock>, now: i64, days_ahead: i64, zone: Tz) -> Vec<Gap> {
+    let time_line = now;
+
+    blocks.retain(|x| {
+        x.dtstart + x.duration.unwrap_or(0) > time_line
+            || x.dtend.unwrap_or(0) > time_line
+            || x.rrule.is_some()
+    });
+    find_the_gaps(blocks, now, days_ahead, zone)
+}
+
+// turns every recurring block into one block per occurrence, repeating at the same wall-clock time in `zone`
+pub fn expand_recurrences(blocks: &[TimeBlock], zone: Tz) -> Vec<TimeBlock> {
+    let mut expanded_blocks: Vec<TimeBlock> = vec![];
+    for block in blocks {
+        if let Some(rrule) = block.rrule.clone() {
+            // Use block.dtstart as the seed
+            let start_tz = zone.timestamp_opt(block.dtstart, 0).unwrap();
+
+            let rruleset = RRuleSet::new(start_tz).rrule(rrule);
+
+            for time in rruleset.all_unchecked() {
+                let mut b = block.clone();
+                b.dtstart = time.timestamp();
+                // shift dtend if duration is defined, otherwise move it along with the start
+                match b.duration {
+                    Some(dur) => b.dtend = Some(b.dtstart + dur),
+                    None => b.dtend = block.dtend.map(|end| end + b.dtstart - block.dtstart),
+                }
+                expanded_blocks.push(b);
+            }
+        } else {
+            expanded_blocks.push(block.clone());
+        }
+    }
+
+    expanded_blocks
+}
+
+// free time from `now` until `days_ahead` days out, around every busy block
+fn find_the_gaps(blocks: &[TimeBlock], now: i64, days_ahead: i64, zone: Tz) -> Vec<Gap> {
+    let busy = expand_recurrences(blocks, zone)
+        .iter()
+        .filter(|b| b.busy)
+        .map(|b| (b.dtstart, block_end(b)))
+        .collect::<Vec<_>>();
+    free_between(busy, now, now + days_ahead * 86400)
+}
+
+fn task_block(task: &Task, start: i64, duration: i64, now: i64) -> TimeBlock {
+    TimeBlock {
+        duration: Some(duration),
+        dtstart: start,
+        dtend: None,
+        rrule: None,
+        uid: task.uuid.clone(),
+        summary: task.description.clone(),
+        dtstamp: DateTime::from_timestamp(now, 0).unwrap_or_default(),
+        task_uuid: Some(task.uuid.clone()),
+        locked: false,
+        batch: vec![],
+        location: None,
+        busy: true,
+        availability: vec![],
+        foreign: false,
+        all_day: false,
+    }
+}
+
+/// Everything besides free time that decides where tasks go.
+pub struct Placement {
+    pub constraints: HashMap<String, Constraints>,
+    pub energy: Curve,
+    // score added when a task follows straight on from another project
+    pub switch_cost: i64,
+    // when the plan is made, stamped on every block
+    pub now: i64,
+}
+
+impl Placement {
+    pub fn new(config_data: &ConfigInfo, tasks: &[Task], now: i64) -> Result<Self> {
+        let zone = config_data.planning.zone()?;
+        let mut constraints = preferences::by_uuid(&config_data.preferences, tasks, zone)?;
+        let hours = preferences::parse_windows(&config_data.planning.working_hours)?;
+        if !hours.is_empty() {
+            for rules in constraints.values_mut() {
+                rules.limit_to_hours(hours.clone());
+            }
+        }
+        Ok(Self {
+            constraints,
+            energy: Curve::new(&config_data.energy, zone)?,
+            switch_cost: config_data.batching.switch_cost,
+            now,
+        })
+    }
+
+    pub fn rules(&self, task: &Task) -> &Constraints {
+        static UNCONSTRAINED: Constraints = Constraints::none();
+        self.constraints.get(&task.uuid).unwrap_or(&UNCONSTRAINED)
+    }
+}
+
+/// Finds the free time around the blocks, less travel between places, and
+/// the rules for placing the tasks in it. Also returns where overflow starts.
+pub fn prepare(
+    config_data: &ConfigInfo,
+    tasks: &[Task],
+    blocks: &mut Vec<TimeBlock>,
+    now: i64,
+) -> Result<(Vec<Gap>, i64, Placement)> {
+    let zone = config_data.planning.zone()?;
+    let mut gaps = free_gaps(blocks, now, config_data.planning.days_ahead, zone);
+    let overflow_start = blocks.last().map(block_end).unwrap_or(now);
+    let places = Places::new(&config_data.travel);
+    let stays = places.apply(&mut gaps, blocks, overflow_start, zone);
+    let mut placement = Placement::new(config_data, tasks, now)?;
+    places.bind(&mut placement.constraints, tasks, &stays);
+    Ok((gaps, overflow_start, placement))
+}
+
+// free time in the gaps between `from` and `to`
+fn free_between_gaps(gaps: &[Gap], from: i64, to: i64) -> i64 {
+    gaps.iter()
+        .map(|g| (g.end.min(to) - g.start.max(from)).max(0))
+        .sum()
+}
+
+// whether running `pick` first still leaves time for every task due before it
+fn keeps_deadlines(pick: &Task, others: &[&Task], gaps: &[Gap], start: i64) -> bool {
+    let mut earlier: Vec<&&Task> = others
+        .iter()
+        .filter(|t| t.uuid != pick.uuid && t.due < pick.due)
+        .collect();
+    earlier.sort_by_key(|t| t.due);
+    let mut work = pick.estimated;
+    earlier.into_iter().all(|t| {
+        work += t.estimated;
+        free_between_gaps(gaps, start, t.due) >= work
+    })
+}
+
+/// Greedily fills the gaps, splitting a task across gaps when it does not
+/// fit. Tasks only go where their time windows allow. At each point the task
+/// that fits best goes first: one that prefers this time of day, matches the
+/// energy level, stays in the same project and does not stack too much heavy
+/// work, then by due date. Deadlines come first: when the best fit would
+/// leave too little time for a task due before it, the earliest due goes.
+/// Whatever is left over is stacked back to back from `overflow_start`, or
+/// from the end of the last placed block.
+pub fn place_tasks(
+    mut tasks: Vec<Task>,
+    gaps: &[Gap],
+    overflow_start: i64,
+    placement: &Placement,
+) -> Vec<TimeBlock> {
+    let rules = |task: &Task| placement.rules(task);
+    let energy = &placement.energy;
+    let mut placed = vec![];
+    // end and project of the last placed block, and how much high energy work ran right up to it
+    let mut last_end = i64::MIN;
+    let mut last_project: Option<String> = None;
+    let mut heavy_run = 0;
+    for gap in gaps {
+        let mut start = gap.start;
+        //need to sort tasks by due date still
+        tasks.sort_by_key(|x| x.due);
+        while start < gap.end {
+            let candidates: Vec<(usize, &Task)> = tasks
+                .iter()
+                .enumerate()
+                .filter(|(_, t)| {
+                    t.status != "scheduled"
+                        && t.start.is_none_or(|s| start >= s)
+                        && rules(t).allowed_at(start)
+                })
+                .collect();
+            let score = |t: &Task| {
+                let follows = start == last_end;
+                let run = if follows { heavy_run } else { 0 };
+                let mut score = energy.score(energy.level_of(t), start, run);
+                if !rules(t).preferred_at(start) {
+                    score += 2;
+                }
+                if follows && t.project != last_project {
+                    score += placement.switch_cost;
+                }
+                score
+            };
+            let best_fit = candidates
+                .iter()
+                .min_by_key(|(_, t)| (score(t), t.due))
+                .copied();
+            let others: Vec<&Task> = candidates.iter().map(|(_, t)| *t).collect();
+            let candidate = match best_fit {
+                Some((_, t)) if !keeps_deadlines(t, &others, gaps, start) => candidates
+                    .iter()
+                    .min_by_key(|(_, t)| (t.due, score(t)))
+                    .copied(),
+                best_fit => best_fit,
+            };
+            let Some((idx, task)) = candidate else {
+                // nothing may run here, skip ahead to where a window opens or a task may start
+                let next = tasks
+                    .iter()
+                    .filter(|t| t.status != "scheduled")
+                    .filter_map(|t| {
+                        let opens = rules(t).next_change(start);
+                        let starts = t.start.filter(|s| *s > start);
+                        opens.into_iter().chain(starts).min()
+                    })
+                    .min();
+                match next {
+                    Some(next) if next < gap.end => {
+                        start = next;
+                        continue;
+                    }
+                    _ => break,
+                }
+            };
+            let task = task.clone();
+            let until = rules(&task).allowed_until(start, gap.end);
+            let time_til = until - start;
+            let length = task.estimated.min(time_til);
+            heavy_run = match energy.level_of(&task) {
+                Level::High if start == last_end => heavy_run + length,
+                Level::High => length,
+                _ => 0,
+            };
+            last_end = start + length;
+            last_project = task.project.clone();
+            if task.estimated > time_til {
+                placed.push(task_block(&task, start, time_til, placement.now));
+                tasks.push(Task {
+                    estimated: task.estimated - time_til,
+                    status: "unscheduled".to_string(),
+                    ..task
+                });
+                start = until;
+            } else {
+                placed.push(task_block(&task, start, task.estimated, placement.now));
+                start += task.estimated;
+            }
+            tasks[idx].status = "scheduled".to_string();
+        }
+    }
+    // leftovers include the remainders of tasks that were split across gaps
+    tasks.retain(|x| x.status != "scheduled");
+    let mut time_line_after = placed.last().map(block_end).unwrap_or(overflow_start);
+    for task in tasks.as_slice() {
+        let rules = rules(task);
+        let mut remaining = task.estimated;
+        while remaining > 0 {
+            // left unplaced, `plan` reports the task as infeasible
+            let Some(start) = rules.next_allowed(time_line_after) else {
+                break;
+            };
+            let until = rules.allowed_until(start, start + remaining);
+            placed.push(task_block(task, start, until - start, placement.now));
+            remaining -= until - start;
+            //update time_line_after
+            time_line_after = until;
+        }
+    }
+    placed
+}
+
+/// Places the tasks around the busy blocks as of `now`. Does no I/O: the
+/// result is meant to be written back through a [`crate::store::CalendarStore`].
+pub fn plan(
+    tasks: Vec<Task>,
+    mut blocks: Vec<TimeBlock>,
+    config: &ConfigInfo,
+    now: i64,
+) -> Result<Schedule> {
+    let places = Places::new(&config.travel);
+    let (to_place, batches) = batch::combine(tasks.clone(), &config.batching, |t| {
+        (
+            preferences::matching(&config.preferences, t),
+            places.place_of_task(t).cloned(),
+        )
+    });
+    let (gaps, overflow_start, placement) = prepare(config, &to_place, &mut blocks, now)?;
+    let (mut placed, report) = match config.solver.mode.as_str() {
+        "optimal" => {
+            schema::optimal_supports(config)?;
+            let heavy = to_place
+                .iter()
+                .any(|t| placement.energy.level_of(t) == Level::High);
+            if heavy && placement.energy.limits_heavy() {
+                return Err(Error::Config(
+                    "solver.mode: optimal does not cap heavy work, set \
+                     energy.max_heavy_minutes = 0 or use greedy"
+                        .to_string(),
+                ));
+            }
+            let (placed, report) = solver::solve(
+                to_place.clone(),
+                &gaps,
+                overflow_start,
+                &config.solver,
+                &placement,
+            );
+            (placed, Some(report))
+        }
+        _ => (
+            place_tasks(to_place.clone(), &gaps, overflow_start, &placement),
+            None,
+        ),
+    };
+    // time is only left unplaced when the task's windows never allow it
+    if let Some(task) = to_place.iter().find(|task| {
+        let placed: i64 = placed
+            .iter()
+            .filter(|b| b.uid == task.uuid)
+            .filter_map(|b| b.duration)
+            .sum();
+        placed < task.estimated
+    }) {
+        return Err(Error::Infeasible(format!(
+            "'{}' has no free time its time windows or place allow",
+            task.description
+        )));
+    }
+    batch::label(&mut placed, &batches);
+    let late = tasks
+        .iter()
+        .filter_map(|task| {
+            let finish = placed
+                .iter()
+                .filter(|b| {
+                    b.task_uuid.as_ref() == Some(&task.uuid) || b.batch.contains(&task.uuid)
+                })
+                .map(block_end)
+                .max()?;
+            (finish > task.due).then(|| Late {
+                uuid: task.uuid.clone(),
+                description: task.description.clone(),
+                due: task.due,
+                finish,
+            })
+        })
+        .collect();
+    blocks.extend(placed.clone());
+    Ok(Schedule {
+        placed,
+        calendar: blocks,
+        late,
+        solver: report,
+    })
+}
+
+/// Tasks with this tag keep all of their blocks where they are.
+pub const PIN_TAG: &str = "pinned";
+
+/// Takes the time already sitting in locked blocks off each task's estimate,
+/// dropping tasks that are fully covered.
+pub fn subtract_locked(tasks: &mut Vec<Task>, blocks: &[TimeBlock]) {
+    take_time(tasks, blocks.iter().filter(|b| b.locked));
+    tasks.retain(|t| t.estimated > 0);
+}
+
+// takes the time in the blocks off the tasks they belong to, a batch's time
+// goes to its members earliest due first
+fn take_time<'a>(tasks: &mut [Task], blocks: impl Iterator<Item = &'a TimeBlock>) {
+    for block in blocks {
+        let mut time = block_end(block) - block.dtstart;
+        if block.batch.is_empty() {
+            if let Some(task) = tasks
+                .iter_mut()
+                .find(|t| block.uid == t.uuid || block.task_uuid.as_ref() == Some(&t.uuid))
+            {
+                task.estimated -= time;
+            }
+            continue;
+        }
+        let mut members: Vec<&mut Task> = tasks
+            .iter_mut()
+            .filter(|t| block.batch.contains(&t.uuid))
+            .collect();
+        members.sort_by_key(|t| t.due);
+        for member in members {
+            let take = time.min(member.estimated.max(0));
+            member.estimated -= take;
+            time -= take;
+        }
+    }
+}
+
+/// How far a replan moved the blocks of the tasks.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Moves {
+    pub moved: usize,
+    /// How many task blocks there were before.
+    pub blocks: usize,
+    /// Seconds.
+    pub average: i64,
+    pub max: i64,
+}
+
+impl Moves {
+    /// Compares each task's blocks before and after, pairing them in start order.
+    pub fn between(before: &[TimeBlock], after: &[TimeBlock]) -> Self {
+        let mut moved = 0;
+        let mut total_shift = 0;
+        let mut max = 0;
+        let mut uuids: Vec<&String> = before.iter().map(|b| &b.uid).collect();
+        uuids.sort();
+        uuids.dedup();
+        for uuid in uuids {
+            let starts = |blocks: &[TimeBlock]| {
+                let mut starts: Vec<i64> = blocks
+                    .iter()
+                    .filter(|b| &b.uid == uuid)
+                    .map(|b| b.dtstart)
+                    .collect();
+                starts.sort();
+                starts
+            };
+            for (old, new) in starts(before).iter().zip(&starts(after)) {
+                let shift = (new - old).abs();
+                if shift > 0 {
+                    moved += 1;
+                    total_shift += shift;
+                    max = max.max(shift);
+                }
+            }
+        }
+        Self {
+            moved,
+            blocks: before.len(),
+            average: if moved > 0 {
+                total_shift / moved as i64
+            } else {
+                0
+            },
+            max,
+        }
+    }
+}
+
+/// What `reschedule` decided.
+#[derive(Debug, Clone)]
+pub struct Rescheduled {
+    /// The plan for the blocks that were let go. Its calendar holds the kept
+    /// blocks too.
+    pub schedule: Schedule,
+    /// Task blocks that stayed where they were.
+    pub kept: Vec<TimeBlock>,
+    pub moves: Moves,
+}
+
+fn overlaps(a: &TimeBlock, b: &TimeBlock) -> bool {
+    a.dtstart < block_end(b) && b.dtstart < block_end(a)
+}
+
+// the estimate may have changed since the task's blocks were placed: places
+// what is missing, or trims the blocks that are free to change, latest first
+fn fit_estimate(
+    task: &Task,
+    task_blocks: &[TimeBlock],
+    kept: &mut Vec<TimeBlock>,
+    to_place: &mut Vec<Task>,
+    freeze_until: i64,
+) {
+    let own: Vec<&TimeBlock> = task_blocks
+        .iter()
+        .filter(|b| b.uid == task.uuid && b.batch.is_empty())
+        .collect();
+    if own.is_empty() {
+        return;
+    }
+    let held: i64 = own.iter().map(|b| block_end(b) - b.dtstart).sum();
+    if held < task.estimated {
+        to_place.push(Task {
+            id: 0,
+            estimated: task.estimated - held,
+            status: "pending".to_string(),
+            start: Some(freeze_until.max(task.start.unwrap_or(i64::MIN))),
+            ..task.clone()
+        });
+        return;
+    }
+    let mut excess = held - task.estimated;
+    let mut released: Vec<&mut Task> = to_place
+        .iter_mut()
+        .filter(|t| t.uuid == task.uuid)
+        .collect();
+    released.sort_by_key(|t| std::cmp::Reverse(t.start));
+    for piece in released {
+        let take = excess.min(piece.estimated);
+        piece.estimated -= take;
+        excess -= take;
+    }
+    to_place.retain(|t| t.estimated > 0);
+    // blocks stable mode kept, never locked, pinned or frozen ones
+    if excess == 0 || task.tags.iter().any(|tag| tag == PIN_TAG) {
+        return;
+    }
+    kept.sort_by_key(|b| std::cmp::Reverse(b.dtstart));
+    for block in kept.iter_mut() {
+        if block.uid != task.uuid
+            || !block.batch.is_empty()
+            || block.locked
+            || block.dtstart < freeze_until
+        {
+            continue;
+        }
+        let length = block_end(block) - block.dtstart;
+        let take = excess.min(length);
+        block.duration = Some(length - take);
+        block.dtend = None;
+        excess -= take;
+    }
+    kept.retain(|b| b.uid != task.uuid || block_end(b) > b.dtstart);
+    kept.sort_by_key(|b| b.dtstart);
+}
+
+/// Replans the blocks of already scheduled `tasks`. Blocks starting within
+/// the freeze window, locked blocks and blocks of pinned tasks stay where they
+/// are, and in stable mode so does every block that does not clash with
+/// another event. The rest is handed back to `plan` as tasks that may not
+/// start before their old block. A task whose estimate changed gets the
+/// missing time placed, or its movable blocks trimmed.
+pub fn reschedule(
+    blocks: Vec<TimeBlock>,
+    tasks: &[Task],
+    config: &ConfigInfo,
+    now: i64,
+) -> Result<Rescheduled> {
+    let freeze_until = now + config.reschedule.freeze_hours * 3600;
+    // a batch block belongs to the tasks it lists, another profile's block stays put
+    let (task_blocks, mut events): (Vec<TimeBlock>, Vec<TimeBlock>) =
+        blocks.into_iter().partition(|block| {
+            !block.foreign
+                && tasks
+                    .iter()
+                    .any(|t| t.uuid == block.uid || block.batch.contains(&t.uuid))
+        });
+    let mut busy = expand_recurrences(&events, config.planning.zone()?);
+    busy.retain(|b| b.busy);
+
+    let mut kept = vec![];
+    let mut to_place = vec![];
+    // the batches let go, by uid, with their members and the earliest they may start
+    let mut batches: Vec<(String, Vec<String>, i64)> = vec![];
+    for block in &task_blocks {
+        let mut owners = tasks
+            .iter()
+            .filter(|t| t.uuid == block.uid || block.batch.contains(&t.uuid));
+        let pinned = owners
+            .clone()
+            .any(|t| t.tags.iter().any(|tag| tag == PIN_TAG));
+        let frozen = block.dtstart < freeze_until;
+        let clashes = busy.iter().any(|event| overlaps(event, block));
+        if block.locked || pinned || frozen || (config.reschedule.stable && !clashes) {
+            kept.push(block.clone());
+            continue;
+        }
+        let start = block.dtstart.max(freeze_until);
+        if !block.batch.is_empty() {
+            match batches.iter_mut().find(|(uid, _, _)| *uid == block.uid) {
+                Some((_, _, earliest)) => *earliest = (*earliest).min(start),
+                None => batches.push((block.uid.clone(), block.batch.clone(), start)),
+            }
+            continue;
+        }
+        let Some(task) = owners.next() else {
+            continue;
+        };
+        to_place.push(Task {
+            id: 0,
+            estimated: block_end(block) - block.dtstart,
+            description: block.summary.clone(),
+            status: "pending".to_string(),
+            start: Some(start),
+            ..task.clone()
+        });
+    }
+    for task in tasks {
+        fit_estimate(task, &task_blocks, &mut kept, &mut to_place, freeze_until);
+    }
+    // split each batch back into its tasks, less the time its kept pieces
+    // already hold; plan combines them again
+    for (uid, members, start) in batches {
+        let mut members: Vec<Task> = tasks
+            .iter()
+            .filter(|t| members.contains(&t.uuid))
+            .map(|t| Task {
+                start: Some(start),
+                ..t.clone()
+            })
+            .collect();
+        take_time(&mut members, kept.iter().filter(|b| b.uid == uid));
+        to_place.extend(members.into_iter().filter(|t| t.estimated > 0));
+    }
+    events.extend(kept.iter().cloned());
+    events.sort_by_key(|b| b.dtstart);
+    let schedule = plan(to_place, events, config, now)?;
+    let after = [schedule.placed.as_slice(), kept.as_slice()].concat();
+    Ok(Rescheduled {
+        moves: Moves::between(&task_blocks, &after),
+        schedule,
+        kept,
+    })
+}
@@ -0,0 +1,288 @@
+use chrono::{DateTime, Datelike, Duration, NaiveTime, TimeZone, Timelike};
+use std::collections::HashMap;
+
+use crate::dates::Tz;
+use crate::structs::{Task, TimePreference};
+use crate::{Error, Result};
+
+// how many window edges to walk before giving up on finding allowed time
+const MAX_STEPS: usize = 64;
+
+/// A daily time window such as `08:00-12:00` or `weekdays 12:00-14:00`.
+/// Windows whose end is before their start run past midnight.
+#[derive(Debug, Clone)]
+pub struct Window {
+    days: [bool; 7],
+    start: u32,
+    end: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct Constraints {
+    only: Vec<Window>,
+    forbidden: Vec<Window>,
+    preferred: Vec<Window>,
+    // working hours, every task stays inside them on top of its own windows
+    hours: Vec<Window>,
+    // absolute (start, end) ranges the task is held to, like the times you are at its place
+    within: Option<Vec<(i64, i64)>>,
+    // the zone the windows are read in
+    zone: Tz,
+}
+
+fn parse_days(spec: &str) -> Result<[bool; 7], String> {
+    let day = |name: &str| -> Result<usize, String> {
+        let idx = match name {
+            "mon" | "monday" => 0,
+            "tue" | "tuesday" => 1,
+            "wed" | "wednesday" => 2,
+            "thu" | "thursday" => 3,
+            "fri" | "friday" => 4,
+            "sat" | "saturday" => 5,
+            "sun" | "sunday" => 6,
+            _ => return Err(format!("unknown day '{name}'")),
+        };
+        Ok(idx)
+    };
+    let mut days = [false; 7];
+    for part in spec.to_lowercase().split(',') {
+        match part.trim() {
+            "weekdays" => days[..5].fill(true),
+            "weekends" => days[5..].fill(true),
+            range if range.contains('-') => {
+                let (from, to) = range.split_once('-').unwrap();
+                let (from, to) = (day(from.trim())?, day(to.trim())?);
+                let mut idx = from;
+                loop {
+                    days[idx] = true;
+                    if idx == to {
+                        break;
+                    }
+                    idx = (idx + 1) % 7;
+                }
+            }
+            name => days[day(name)?] = true,
+        }
+    }
+    Ok(days)
+}
+
+fn parse_time(time: &str) -> Result<u32, String> {
+    if time.trim() == "24:00" {
+        return Ok(86400);
+    }
+    NaiveTime::parse_from_str(time.trim(), "%H:%M")
+        .map(|t| t.num_seconds_from_midnight())
+        .map_err(|_| format!("invalid time '{time}', expected HH:MM"))
+}
+
+impl Window {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        let (days, times) = match input.rsplit_once(' ') {
+            Some((days, times)) => (parse_days(days)?, times),
+            None => ([true; 7], input),
+        };
+        let (start, end) = times
+            .split_once('-')
+            .ok_or_else(|| format!("invalid window '{input}', expected HH:MM-HH:MM"))?;
+        Ok(Self {
+            days,
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        })
+    }
+
+    pub fn contains<Z: TimeZone>(&self, time: &DateTime<Z>) -> bool {
+        let secs = time.num_seconds_from_midnight();
+        let today = time.weekday().num_days_from_monday() as usize;
+        if self.start < self.end {
+            self.days[today] && self.start <= secs && secs < self.end
+        } else {
+            let yesterday = (today + 6) % 7;
+            (self.days[today] && secs >= self.start) || (self.days[yesterday] && secs < self.end)
+        }
+    }
+
+    // the window's start and end on the days around `time`, as timestamps
+    fn edges<Z: TimeZone>(&self, time: &DateTime<Z>) -> Vec<i64> {
+        let mut edges = vec![];
+        for offset in -1..=1 {
+            let date = time.date_naive() + Duration::days(offset);
+            for secs in [self.start, self.end] {
+                let naive = date.and_time(NaiveTime::MIN) + Duration::seconds(secs as i64);
+                if let Some(edge) = time.timezone().from_local_datetime(&naive).earliest() {
+                    edges.push(edge.timestamp());
+                }
+            }
+        }
+        edges
+    }
+}
+
+impl Constraints {
+    pub const fn none() -> Self {
+        Self {
+            only: vec![],
+            forbidden: vec![],
+            preferred: vec![],
+            hours: vec![],
+            within: None,
+            zone: Tz::LOCAL,
+        }
+    }
+
+    fn local(&self, timestamp: i64) -> DateTime<Tz> {
+        self.zone.timestamp_opt(timestamp, 0).unwrap()
+    }
+
+    pub fn limit_to(&mut self, ranges: Vec<(i64, i64)>) {
+        self.within = Some(ranges);
+    }
+
+    pub fn limit_to_hours(&mut self, hours: Vec<Window>) {
+        self.hours = hours;
+    }
+
+    fn windows(&self) -> impl Iterator<Item = &Window> {
+        self.only
+            .iter()
+            .chain(&self.forbidden)
+            .chain(&self.preferred)
+            .chain(&self.hours)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows().next().is_none() && self.within.is_none()
+    }
+
+    pub fn allowed_at(&self, timestamp: i64) -> bool {
+        let time = self.local(timestamp);
+        (self.only.is_empty() || self.only.iter().any(|w| w.contains(&time)))
+            && (self.hours.is_empty() || self.hours.iter().any(|w| w.contains(&time)))
+            && !self.forbidden.iter().any(|w| w.contains(&time))
+            && self.within.as_ref().is_none_or(|ranges| {
+                ranges
+                    .iter()
+                    .any(|(start, end)| *start <= timestamp && timestamp < *end)
+            })
+    }
+
+    pub fn preferred_at(&self, timestamp: i64) -> bool {
+        let time = self.local(timestamp);
+        self.preferred.iter().any(|w| w.contains(&time))
+    }
+
+    // the next instant after `timestamp` where any of the windows or ranges opens or closes
+    fn next_edge(&self, timestamp: i64) -> i64 {
+        let time = self.local(timestamp);
+        let ranges = self.within.iter().flatten().flat_map(|(s, e)| [*s, *e]);
+        self.windows()
+            .flat_map(|w| w.edges(&time))
+            .chain(ranges)
+            .filter(|edge| *edge > timestamp)
+            .min()
+            .unwrap_or(timestamp + 86400)
+    }
+
+    /// How long a task may keep running from `timestamp`, capped at `limit`.
+    pub fn allowed_until(&self, timestamp: i64, limit: i64) -> i64 {
+        if self.is_empty() {
+            return limit;
+        }
+        let mut cursor = timestamp;
+        for _ in 0..MAX_STEPS {
+            let edge = self.next_edge(cursor);
+            if edge >= limit {
+                return limit;
+            }
+            if !self.allowed_at(edge) {
+                return edge;
+            }
+            cursor = edge;
+        }
+        limit
+    }
+
+    /// The first instant at or after `timestamp` the task is allowed to run,
+    /// or None when the windows never allow it.
+    pub fn next_allowed(&self, timestamp: i64) -> Option<i64> {
+        let mut cursor = timestamp;
+        for _ in 0..MAX_STEPS {
+            if self.allowed_at(cursor) {
+                return Some(cursor);
+            }
+            cursor = self.next_edge(cursor);
+        }
+        None
+    }
+
+    /// The next instant after `timestamp` where being allowed or preferred may change.
+    pub fn next_change(&self, timestamp: i64) -> Option<i64> {
+        (!self.is_empty()).then(|| self.next_edge(timestamp))
+    }
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+fn matches(pref: &TimePreference, task: &Task) -> bool {
+    let tag = pref
+        .tag
+        .as_ref()
+        .is_some_and(|tag| task.tags.iter().any(|t| t == tag));
+    // a preference for project "work" also covers "work.reports"
+    let project = pref.project.as_ref().is_some_and(|project| {
+        task.project
+            .as_ref()
+            .is_some_and(|p| p == project || p.starts_with(&format!("{project}.")))
+    });
+    tag || project
+}
+
+pub fn parse_windows(windows: &[String]) -> Result<Vec<Window>> {
+    windows
+        .iter()
+        .map(|w| Window::parse(w).map_err(|e| Error::Config(format!("bad time window: {e}"))))
+        .collect()
+}
+
+/// The positions of the preferences that apply to the task.
+pub fn matching(preferences: &[TimePreference], task: &Task) -> Vec<usize> {
+    (0..preferences.len())
+        .filter(|&i| matches(&preferences[i], task))
+        .collect()
+}
+
+/// Merges every preference that applies to the task through its tags or
+/// project. The windows are read as wall-clock time in `zone`.
+pub fn for_task(preferences: &[TimePreference], task: &Task, zone: Tz) -> Result<Constraints> {
+    let mut constraints = Constraints {
+        zone,
+        ..Constraints::none()
+    };
+    for pref in preferences.iter().filter(|p| matches(p, task)) {
+        constraints.only.extend(parse_windows(&pref.only)?);
+        constraints
+            .forbidden
+            .extend(parse_windows(&pref.forbidden)?);
+        constraints
+            .preferred
+            .extend(parse_windows(&pref.preferred)?);
+    }
+    Ok(constraints)
+}
+
+pub fn by_uuid(
+    preferences: &[TimePreference],
+    tasks: &[Task],
+    zone: Tz,
+) -> Result<HashMap<String, Constraints>> {
+    tasks
+        .iter()
+        .map(|t| Ok((t.uuid.clone(), for_task(preferences, t, zone)?)))
+        .collect()
+}
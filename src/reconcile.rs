@@ -0,0 +1,83 @@
+use chrono::Utc;
+use std::collections::HashMap;
+
+use crate::dates::parse_datetime_in;
+use crate::plan::block_end;
+use crate::structs::*;
+
+/// What `reconcile` changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Reconciled {
+    pub removed: usize,
+    pub shrunk: usize,
+    /// Seconds of calendar time handed back.
+    pub freed: i64,
+}
+
+// when the task stopped needing calendar time, or None if it is still open
+fn finished_at(task_uuid: &str, states: &HashMap<String, TaskState>, now: i64) -> Option<i64> {
+    match states.get(task_uuid) {
+        Some(state) if state.status == "completed" || state.status == "deleted" => Some(
+            state
+                .end
+                .as_deref()
+                // taskwarrior exports its dates in UTC
+                .and_then(|end| parse_datetime_in(end, &Utc).ok())
+                .unwrap_or(now),
+        ),
+        Some(_) => None,
+        // the block was written by cronwave but the task is gone from taskwarrior
+        None => Some(now),
+    }
+}
+
+/// Drops or shortens blocks belonging to tasks that are completed, deleted or
+/// missing from `states`, every task keyed by uuid. Blocks that already ran
+/// before the task finished are kept as history; the caller reschedules to
+/// hand the freed time to other tasks.
+pub fn reconcile(
+    blocks: &mut Vec<TimeBlock>,
+    states: &HashMap<String, TaskState>,
+    now: i64,
+) -> Reconciled {
+    let mut result = Reconciled::default();
+    blocks.retain_mut(|block| {
+        if block.rrule.is_some() {
+            return true;
+        }
+        let task_uuid = match &block.task_uuid {
+            Some(uuid) => uuid.clone(),
+            None if states.contains_key(&block.uid) => block.uid.clone(),
+            None => return true,
+        };
+        // a batch is done once every task in it is
+        let finished = if block.batch.is_empty() {
+            finished_at(&task_uuid, states, now)
+        } else {
+            block
+                .batch
+                .iter()
+                .map(|uuid| finished_at(uuid, states, now))
+                .collect::<Option<Vec<i64>>>()
+                .and_then(|ends| ends.into_iter().max())
+        };
+        let Some(finished) = finished else {
+            return true;
+        };
+        let end = block_end(block);
+        if end <= finished {
+            true
+        } else if block.dtstart >= finished {
+            result.removed += 1;
+            result.freed += end - block.dtstart;
+            false
+        } else {
+            result.shrunk += 1;
+            result.freed += end - finished;
+            block.duration = Some(finished - block.dtstart);
+            block.dtend = None;
+            true
+        }
+    });
+    result
+}
@@ -0,0 +1,81 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+use crate::plan::{block_end, place_tasks, prepare};
+use crate::structs::*;
+use crate::Result;
+
+// without three point UDAs, assume tasks run a bit short at best and well over at worst
+const DEFAULT_OPTIMISTIC: f64 = 0.75;
+const DEFAULT_PESSIMISTIC: f64 = 1.75;
+
+fn three_point(task: &Task) -> ThreePoint {
+    task.three_point.unwrap_or(ThreePoint {
+        optimistic: (task.estimated as f64 * DEFAULT_OPTIMISTIC) as i64,
+        likely: task.estimated,
+        pessimistic: (task.estimated as f64 * DEFAULT_PESSIMISTIC) as i64,
+    })
+}
+
+// inverse cdf of the triangular distribution over optimistic..pessimistic peaking at likely
+fn sample(range: ThreePoint, rng: &mut impl Rng) -> i64 {
+    let (a, m, b) = (
+        range.optimistic as f64,
+        range.likely as f64,
+        range.pessimistic as f64,
+    );
+    if b <= a {
+        return range.likely;
+    }
+    let u: f64 = rng.random();
+    let c = (m - a) / (b - a);
+    let value = if u < c {
+        a + (u * (b - a) * (m - a)).sqrt()
+    } else {
+        b - ((1.0 - u) * (b - a) * (b - m)).sqrt()
+    };
+    value.round() as i64
+}
+
+/// Replays the greedy placement `runs` times with task durations drawn around
+/// their estimates. Returns each task's finish time in every run, sorted. The
+/// same `seed` gives the same draws.
+pub fn finishes(
+    tasks: &[Task],
+    mut blocks: Vec<TimeBlock>,
+    runs: usize,
+    config: &ConfigInfo,
+    now: i64,
+    seed: u64,
+) -> Result<HashMap<String, Vec<i64>>> {
+    let (gaps, overflow_start, placement) = prepare(config, tasks, &mut blocks, now)?;
+    let ranges: Vec<ThreePoint> = tasks.iter().map(three_point).collect();
+    let mut finishes: HashMap<String, Vec<i64>> = HashMap::new();
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    for _ in 0..runs {
+        let sampled: Vec<Task> = tasks
+            .iter()
+            .zip(&ranges)
+            .map(|(task, range)| {
+                let mut task = task.clone();
+                task.estimated = sample(*range, &mut rng);
+                task
+            })
+            .collect();
+        let mut run_finish: HashMap<String, i64> = HashMap::new();
+        for block in place_tasks(sampled, &gaps, overflow_start, &placement) {
+            let end = block_end(&block);
+            let finish = run_finish.entry(block.uid).or_insert(end);
+            *finish = (*finish).max(end);
+        }
+        for (uuid, finish) in run_finish {
+            finishes.entry(uuid).or_default().push(finish);
+        }
+    }
+    for runs_finish in finishes.values_mut() {
+        runs_finish.sort();
+    }
+    Ok(finishes)
+}
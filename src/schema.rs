@@ -0,0 +1,349 @@
+use toml::{Table, Value};
+
+use crate::energy::Level;
+use crate::preferences::parse_windows;
+use crate::structs::ConfigInfo;
+use crate::{Error, Result};
+
+/// The config version this build writes. Bump it together with a new step in
+/// `migrate` whenever a key is renamed or moved.
+pub const VERSION: u32 = 2;
+
+fn rename(table: &mut Table, from: &str, to: &str) {
+    if let Some(value) = table.remove(from) {
+        table.entry(to).or_insert(value);
+    }
+}
+
+// version 1 had no `version` key and called the sections Basic and main
+fn from_v1(table: &mut Table) {
+    rename(table, "Basic", "caldav");
+    rename(table, "main", "planning");
+}
+
+/// Brings an older config up to `VERSION`. Returns the version it was at.
+pub fn migrate(table: &mut Table) -> Result<u32> {
+    let from = match table.get("version") {
+        None => 1,
+        Some(Value::Integer(version)) if *version >= 1 => *version as u32,
+        Some(other) => {
+            return Err(Error::Config(format!(
+                "version: expected a positive number, found {other}"
+            )))
+        }
+    };
+    if from > VERSION {
+        return Err(Error::Config(format!(
+            "version: the config is version {from}, this cronwave only reads up to {VERSION}"
+        )));
+    }
+    if from < 2 {
+        from_v1(table);
+    }
+    table.insert("version".to_string(), Value::Integer(VERSION as i64));
+    Ok(from)
+}
+
+/// Migrates the text of an older config file. Returns the version it was at
+/// and the new text, or None when it is already current.
+pub fn migrate_text(text: &str) -> Result<Option<(u32, String)>> {
+    let mut table: Table = text.parse().map_err(|e| Error::Config(format!("{e}")))?;
+    let from = migrate(&mut table)?;
+    if from == VERSION {
+        return Ok(None);
+    }
+    let text = toml::to_string(&table).map_err(|e| Error::Config(e.to_string()))?;
+    Ok(Some((from, text)))
+}
+
+/// Reads a current config. Unknown keys and values of the wrong type are
+/// errors that name the key and its line.
+pub fn parse(text: &str) -> Result<ConfigInfo> {
+    toml::from_str(text).map_err(|e| Error::Config(format!("{e}")))
+}
+
+// a setting that only takes one of a few words
+fn one_of(key: &str, value: &str, allowed: &[&str]) -> Result<()> {
+    if allowed.contains(&value) {
+        Ok(())
+    } else {
+        Err(Error::Config(format!(
+            "{key}: '{value}', expected one of {}",
+            allowed.join(", ")
+        )))
+    }
+}
+
+// prefixes a window or level error with the key it came from
+fn at<T>(key: &str, result: Result<T>) -> Result<T> {
+    result.map_err(|e| match e {
+        Error::Config(message) => Error::Config(format!("{key}: {message}")),
+        other => other,
+    })
+}
+
+// the optimal solver only weighs lateness and splits, so refuse what it would silently ignore
+pub(crate) fn optimal_supports(config: &ConfigInfo) -> Result<()> {
+    let energy = &config.energy;
+    let ignored = if !energy.high.is_empty() || !energy.low.is_empty() || !energy.tags.is_empty() {
+        Some("energy windows and tags")
+    } else if config.batching.switch_cost != 0 {
+        Some("batching.switch_cost")
+    } else if config.preferences.iter().any(|p| !p.preferred.is_empty()) {
+        Some("preferred windows")
+    } else {
+        None
+    };
+    match ignored {
+        Some(what) => Err(Error::Config(format!(
+            "solver.mode: optimal does not weigh {what}, use greedy or remove them"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Checks the values serde can't, such as time windows and the words some
+/// settings take, so mistakes show up before anything is fetched.
+pub fn validate(config: &ConfigInfo) -> Result<()> {
+    if config.version != VERSION {
+        return Err(Error::Config(format!(
+            "version: expected {VERSION}, found {}",
+            config.version
+        )));
+    }
+    if config.caldav.cal_url == ConfigInfo::default().caldav.cal_url {
+        return Err(Error::Config(
+            "caldav.cal_url: no calendar is set up yet, run `cronwave init`".to_string(),
+        ));
+    }
+    if config.planning.days_ahead <= 0 {
+        return Err(Error::Config(
+            "planning.days_ahead: must be at least 1".to_string(),
+        ));
+    }
+    at(
+        "planning.working_hours",
+        parse_windows(&config.planning.working_hours),
+    )?;
+    config.planning.zone()?;
+    for (i, pref) in config.preferences.iter().enumerate() {
+        at(&format!("preferences[{i}].only"), parse_windows(&pref.only))?;
+        at(
+            &format!("preferences[{i}].forbidden"),
+            parse_windows(&pref.forbidden),
+        )?;
+        at(
+            &format!("preferences[{i}].preferred"),
+            parse_windows(&pref.preferred),
+        )?;
+    }
+    at("energy.high", parse_windows(&config.energy.high))?;
+    at("energy.low", parse_windows(&config.energy.low))?;
+    for (tag, level) in &config.energy.tags {
+        Level::parse(level).map_err(|e| Error::Config(format!("energy.tags.{tag}: {e}")))?;
+    }
+    one_of("solver.mode", &config.solver.mode, &["greedy", "optimal"])?;
+    if config.solver.mode == "optimal" {
+        optimal_supports(config)?;
+    }
+    one_of(
+        "availability.tentative",
+        &config.availability.tentative,
+        &["busy", "free"],
+    )?;
+    one_of(
+        "availability.all_day",
+        &config.availability.all_day,
+        &["busy", "free"],
+    )?;
+    Ok(())
+}
+
+/// Where a resolved setting came from, lowest layer first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File,
+    /// The environment variable that set it.
+    Env(String),
+    /// A `--set key=value` flag.
+    Flag,
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => f.write_str("default"),
+            Source::File => f.write_str("config file"),
+            Source::Env(name) => write!(f, "env {name}"),
+            Source::Flag => f.write_str("--set"),
+        }
+    }
+}
+
+/// The effective config and, per dotted key, its value and where it came from.
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    pub config: ConfigInfo,
+    pub values: Vec<(String, Value, Source)>,
+}
+
+// settings never shown in full
+const SECRET_KEYS: [&str; 1] = ["caldav.cal_pass"];
+
+pub const ENV_PREFIX: &str = "CRONWAVE_";
+
+pub fn is_secret(key: &str) -> bool {
+    SECRET_KEYS.contains(&key)
+}
+
+// every leaf of the table as a dotted key, arrays count as one value
+fn flatten(table: &Table, prefix: &str, out: &mut Vec<(String, Value)>) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            Value::Table(inner) => flatten(inner, &path, out),
+            leaf => out.push((path, leaf.clone())),
+        }
+    }
+}
+
+fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let (path, last) = match key.rsplit_once('.') {
+        Some((path, last)) => (Some(path), last),
+        None => (None, key),
+    };
+    let mut table = table;
+    for part in path.into_iter().flat_map(|p| p.split('.')) {
+        table = table.get(part)?.as_table()?;
+    }
+    table.get(last)
+}
+
+fn insert(table: &mut Table, key: &str, value: Value) -> Result<()> {
+    let mut parts: Vec<&str> = key.split('.').collect();
+    let last = parts.pop().unwrap_or_default();
+    let mut table = table;
+    for part in parts {
+        table = table
+            .entry(part)
+            .or_insert_with(|| Value::Table(Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| Error::Config(format!("{key}: {part} is not a section")))?;
+    }
+    table.insert(last.to_string(), value);
+    Ok(())
+}
+
+// reads an override as the type the key already has, strings by default
+fn typed(key: &str, current: Option<&Value>, raw: &str) -> Result<Value> {
+    let bad = |expected: &str| Error::Config(format!("{key}: expected {expected}, found '{raw}'"));
+    Ok(match current {
+        None | Some(Value::String(_)) => Value::String(raw.to_string()),
+        Some(Value::Integer(_)) => {
+            Value::Integer(raw.trim().parse().map_err(|_| bad("a whole number"))?)
+        }
+        Some(Value::Float(_)) => Value::Float(raw.trim().parse().map_err(|_| bad("a number"))?),
+        Some(Value::Boolean(_)) => {
+            Value::Boolean(raw.trim().parse().map_err(|_| bad("true or false"))?)
+        }
+        Some(_) => format!("value = {raw}")
+            .parse::<Table>()
+            .ok()
+            .and_then(|mut t| t.remove("value"))
+            .ok_or_else(|| bad("a TOML value such as [\"a\", \"b\"]"))?,
+    })
+}
+
+// CRONWAVE_PLANNING_DAYS_AHEAD is planning.days_ahead: the longest section
+// name that fits wins, and the rest is the key within it
+fn env_key(name: &str, sections: &[&String]) -> Option<String> {
+    let rest = name.strip_prefix(ENV_PREFIX)?.to_lowercase();
+    sections
+        .iter()
+        .filter_map(|section| {
+            let key = rest.strip_prefix(section.as_str())?.strip_prefix('_')?;
+            (!key.is_empty()).then(|| format!("{section}.{key}"))
+        })
+        .max_by_key(|key| key.len() - key.rsplit_once('.').map_or(0, |(_, k)| k.len()))
+}
+
+/// Layers the defaults, the config file, `CRONWAVE_*` environment variables
+/// and `key=value` flags, each overriding the ones before it. Environment
+/// variables name a section and a key, such as `CRONWAVE_CALDAV_CAL_URL`;
+/// flags use the dotted key, such as `planning.days_ahead=7`.
+pub fn resolve(file: &str, env: &[(String, String)], flags: &[String]) -> Result<Resolved> {
+    // errors in the file itself are reported with their line
+    parse(file)?;
+    let defaults = match Value::try_from(ConfigInfo::default()) {
+        Ok(Value::Table(table)) => table,
+        _ => Table::new(),
+    };
+    let file: Table = file.parse().map_err(|e| Error::Config(format!("{e}")))?;
+
+    let mut merged = defaults.clone();
+    let mut sources: Vec<(String, Source)> = vec![];
+    let mut set = |merged: &mut Table, key: &str, value: Value, source: Source| {
+        sources.retain(|(k, _)| k != key && !k.starts_with(&format!("{key}.")));
+        sources.push((key.to_string(), source));
+        insert(merged, key, value)
+    };
+    let mut leaves = vec![];
+    flatten(&file, "", &mut leaves);
+    for (key, value) in leaves {
+        set(&mut merged, &key, value, Source::File)?;
+    }
+    let sections: Vec<&String> = defaults
+        .iter()
+        .filter(|(_, value)| value.is_table())
+        .map(|(key, _)| key)
+        .collect();
+    let mut env: Vec<&(String, String)> = env.iter().collect();
+    env.sort();
+    for (name, raw) in env {
+        let Some(key) = env_key(name, &sections) else {
+            continue;
+        };
+        let value = typed(&key, lookup(&merged, &key), raw)?;
+        set(&mut merged, &key, value, Source::Env(name.clone()))?;
+    }
+    for flag in flags {
+        let (key, raw) = flag
+            .split_once('=')
+            .ok_or_else(|| Error::Config(format!("--set {flag}: expected key=value")))?;
+        let key = key.trim();
+        let value = typed(key, lookup(&merged, key), raw)?;
+        set(&mut merged, key, value, Source::Flag)?;
+    }
+
+    // the file was fine on its own, so a mistake here is in an override: name it
+    let config: ConfigInfo = Value::Table(merged.clone()).try_into().map_err(|e| {
+        let message = e.to_string();
+        let culprit = sources.iter().rev().find(|(key, source)| {
+            let last = key.rsplit('.').next().unwrap_or(key);
+            *source != Source::File && message.contains(&format!("`{last}`"))
+        });
+        match culprit {
+            Some((key, source)) => Error::Config(format!("{key} from {source}: {message}")),
+            None => Error::Config(message),
+        }
+    })?;
+    let mut values = vec![];
+    flatten(&merged, "", &mut values);
+    let values = values
+        .into_iter()
+        .map(|(key, value)| {
+            let source = sources
+                .iter()
+                .rev()
+                .find(|(k, _)| *k == key || key.starts_with(&format!("{k}.")))
+                .map_or(Source::Default, |(_, source)| source.clone());
+            (key, value, source)
+        })
+        .collect();
+    Ok(Resolved { config, values })
+}
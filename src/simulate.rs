@@ -0,0 +1,63 @@
+use chrono::TimeZone;
+use cronwave::clock::Clock;
+use cronwave::dates::Tz;
+use cronwave::risk;
+use cronwave::structs::*;
+use cronwave::Result;
+
+fn percentile(sorted: &[i64], p: f64) -> i64 {
+    let idx = ((sorted.len() as f64 * p).ceil() as usize).clamp(1, sorted.len()) - 1;
+    sorted[idx]
+}
+
+fn format_time(timestamp: i64, zone: Tz) -> String {
+    zone.timestamp_opt(timestamp, 0)
+        .unwrap()
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Drops the blocks cronwave placed for these tasks so they can be replanned.
+pub fn without_task_blocks(blocks: Vec<TimeBlock>, tasks: &[Task]) -> Vec<TimeBlock> {
+    blocks
+        .into_iter()
+        .filter(|b| b.task_uuid.is_none() && !tasks.iter().any(|t| t.uuid == b.uid))
+        .collect()
+}
+
+/// Replays the greedy placement `runs` times with task durations drawn around
+/// their estimates and prints, per task, the chance of finishing by `due` and
+/// the P50/P90 completion times. The same `seed` gives the same numbers.
+pub fn simulate(
+    tasks: Vec<Task>,
+    blocks: Vec<TimeBlock>,
+    runs: usize,
+    seed: u64,
+    config_data: &ConfigInfo,
+    clock: &dyn Clock,
+) -> Result<()> {
+    let finishes = risk::finishes(&tasks, blocks, runs, config_data, clock.now(), seed)?;
+    let zone = config_data.planning.zone()?;
+    println!("seed {seed}, rerun with --seed {seed} to get the same numbers");
+    println!(
+        "{:<32} {:<16} {:>7} {:<16} {:<16}",
+        "task", "due", "on time", "P50", "P90"
+    );
+    let mut tasks = tasks;
+    tasks.sort_by_key(|t| t.due);
+    for task in &tasks {
+        let Some(runs_finish) = finishes.get(&task.uuid) else {
+            continue;
+        };
+        let on_time = runs_finish.iter().filter(|f| **f <= task.due).count();
+        println!(
+            "{:<32} {:<16} {:>6.1}% {:<16} {:<16}",
+            task.description.chars().take(32).collect::<String>(),
+            format_time(task.due, zone),
+            on_time as f64 * 100.0 / runs_finish.len() as f64,
+            format_time(percentile(runs_finish, 0.5), zone),
+            format_time(percentile(runs_finish, 0.9), zone),
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use chrono::DateTime;
+use std::time::{Duration, Instant};
+
+use crate::plan::{block_end, place_tasks, Placement};
+use crate::preferences::Constraints;
+use crate::structs::*;
+
+/// How the optimal search went compared to the greedy plan.
+#[derive(Debug, Clone)]
+pub struct SolverReport {
+    pub greedy_cost: i64,
+    pub cost: i64,
+    pub nodes: u64,
+    /// False when nothing better than greedy was found within the time budget.
+    pub improved: bool,
+}
+
+// a task's share of the calendar, as (start, end) pieces
+type Pieces = Vec<(i64, i64)>;
+
+struct Search<'a> {
+    tasks: &'a [Task],
+    placement: &'a Placement,
+    split_penalty: i64,
+    deadline: Instant,
+    best_cost: i64,
+    best: Option<Vec<(usize, Pieces)>>,
+    nodes: u64,
+}
+
+// lateness in seconds plus the penalty for every extra piece a task is cut into
+fn task_cost(task: &Task, pieces: &[(i64, i64)], split_penalty: i64) -> i64 {
+    let finish = pieces.iter().map(|p| p.1).max().unwrap_or(0);
+    let lateness = (finish - task.due).max(0);
+    lateness + split_penalty * (pieces.len() as i64 - 1).max(0)
+}
+
+// puts the task into the earliest free time it is allowed to use, carving it out of `free`
+fn fill(task: &Task, rules: &Constraints, free: &mut Vec<Gap>) -> Pieces {
+    let earliest = task.start.unwrap_or(i64::MIN);
+    let mut remaining = task.estimated;
+    let mut pieces = vec![];
+    let mut i = 0;
+    while remaining > 0 && i < free.len() {
+        let gap = free[i].clone();
+        let start = match rules.next_allowed(gap.start.max(earliest)) {
+            Some(start) if start < gap.end => start,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        let take = remaining.min(rules.allowed_until(start, gap.end) - start);
+        pieces.push((start, start + take));
+        remaining -= take;
+        let mut rest = Vec::with_capacity(2);
+        // the part before the piece is skipped, the part after may still take more of the task
+        let before = usize::from(gap.start < start);
+        if before == 1 {
+            rest.push(Gap {
+                start: gap.start,
+                end: start,
+            });
+        }
+        if start + take < gap.end {
+            rest.push(Gap {
+                start: start + take,
+                end: gap.end,
+            });
+        }
+        free.splice(i..=i, rest);
+        i += before;
+    }
+    pieces
+}
+
+impl Search<'_> {
+    fn branch(
+        &mut self,
+        free: &[Gap],
+        remaining: &mut Vec<usize>,
+        placed: &mut Vec<(usize, Pieces)>,
+        cost: i64,
+    ) {
+        self.nodes += 1;
+        if cost >= self.best_cost {
+            return;
+        }
+        if remaining.is_empty() {
+            self.best_cost = cost;
+            self.best = Some(placed.clone());
+            return;
+        }
+        for pos in 0..remaining.len() {
+            if Instant::now() > self.deadline {
+                return;
+            }
+            let idx = remaining.remove(pos);
+            let mut free = free.to_vec();
+            let task = &self.tasks[idx];
+            let pieces = fill(task, self.placement.rules(task), &mut free);
+            let added = task_cost(&self.tasks[idx], &pieces, self.split_penalty);
+            placed.push((idx, pieces));
+            self.branch(&free, remaining, placed, cost + added);
+            placed.pop();
+            remaining.insert(pos, idx);
+        }
+    }
+}
+
+fn to_blocks(tasks: &[Task], placed: Vec<(usize, Pieces)>, now: i64) -> Vec<TimeBlock> {
+    let mut blocks: Vec<TimeBlock> = placed
+        .into_iter()
+        .flat_map(|(idx, pieces)| {
+            let task = &tasks[idx];
+            pieces.into_iter().map(move |(start, end)| TimeBlock {
+                duration: Some(end - start),
+                dtstart: start,
+                dtend: None,
+                rrule: None,
+                uid: task.uuid.clone(),
+                summary: task.description.clone(),
+                dtstamp: DateTime::from_timestamp(now, 0).unwrap_or_default(),
+                task_uuid: Some(task.uuid.clone()),
+                locked: false,
+                batch: vec![],
+                location: None,
+                busy: true,
+                availability: vec![],
+                foreign: false,
+                all_day: false,
+            })
+        })
+        .collect();
+    blocks.sort_by_key(|b| b.dtstart);
+    blocks
+}
+
+fn plan_cost(tasks: &[Task], blocks: &[TimeBlock], split_penalty: i64) -> i64 {
+    tasks
+        .iter()
+        .map(|task| {
+            let pieces: Pieces = blocks
+                .iter()
+                .filter(|b| b.uid == task.uuid)
+                .map(|b| (b.dtstart, block_end(b)))
+                .collect();
+            task_cost(task, &pieces, split_penalty)
+        })
+        .sum()
+}
+
+/// Branch-and-bound over the order tasks are placed in, minimizing total
+/// lateness plus a penalty per split. Starts from the greedy plan and keeps it
+/// if nothing better is found within the time budget.
+pub fn solve(
+    tasks: Vec<Task>,
+    gaps: &[Gap],
+    overflow_start: i64,
+    solver: &Solver,
+    placement: &Placement,
+) -> (Vec<TimeBlock>, SolverReport) {
+    let split_penalty = solver.split_penalty_minutes * 60;
+    let greedy = place_tasks(tasks.clone(), gaps, overflow_start, placement);
+    let greedy_cost = plan_cost(&tasks, &greedy, split_penalty);
+
+    // anything that does not fit in a gap goes after the last busy block, like the greedy overflow
+    let mut free = gaps.to_vec();
+    free.push(Gap {
+        start: overflow_start.max(gaps.last().map(|g| g.end).unwrap_or(i64::MIN)),
+        end: i64::MAX,
+    });
+    let mut order: Vec<usize> = (0..tasks.len()).collect();
+    // earliest due first, so the first complete dive is already a good incumbent
+    order.sort_by_key(|&idx| tasks[idx].due);
+
+    let mut search = Search {
+        tasks: &tasks,
+        placement,
+        split_penalty,
+        deadline: Instant::now() + Duration::from_millis(solver.time_budget_ms),
+        best_cost: greedy_cost,
+        best: None,
+        nodes: 0,
+    };
+    search.branch(&free, &mut order, &mut vec![], 0);
+
+    let report = SolverReport {
+        greedy_cost,
+        cost: search.best_cost,
+        nodes: search.nodes,
+        improved: search.best.is_some(),
+    };
+    match search.best {
+        Some(best) => (to_blocks(&tasks, best, placement.now), report),
+        None => (greedy, report),
+    }
+}
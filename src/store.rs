@@ -0,0 +1,19 @@
+use crate::structs::{Task, TimeBlock};
+use crate::Result;
+
+/// Where tasks come from and where their scheduled state goes, taskwarrior in the CLI.
+pub trait TaskStore {
+    /// Pending tasks that still need time on the calendar.
+    fn unscheduled(&self) -> Result<Vec<Task>>;
+    /// Pending tasks that already have blocks on the calendar.
+    fn scheduled(&self) -> Result<Vec<Task>>;
+    /// Records that these tasks now have blocks.
+    fn mark_scheduled(&self, uuids: &[String]) -> Result<()>;
+}
+
+/// The calendar blocks are read from and written back to, CalDAV in the CLI.
+pub trait CalendarStore {
+    fn load(&self) -> Result<Vec<TimeBlock>>;
+    /// Replaces the calendar with these blocks.
+    fn save(&self, blocks: &[TimeBlock]) -> Result<()>;
+}
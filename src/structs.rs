@@ -1,9 +1,10 @@
-use chrono::TimeZone;
-
-use chrono::{DateTime, Duration, Local, NaiveDateTime, Utc};
+use chrono::{DateTime, Utc};
 use icalendar::DatePerhapsTime;
-use rrule::{RRule, RRuleSet, Unvalidated, Validated};
+use rrule::{RRule, Unvalidated, Validated};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::dates::{zone, Tz};
 
 #[derive(Debug, Clone)]
 pub struct Task {
@@ -15,6 +16,19 @@ pub struct Task {
     pub status: String,
     pub urgency: f32,
     pub start: Option<i64>,
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+    pub three_point: Option<ThreePoint>,
+    // high, medium or low, from the UDA of the same name
+    pub energy: Option<String>,
+}
+
+// optimistic/likely/pessimistic durations in seconds, from the UDAs of the same name
+#[derive(Debug, Clone, Copy)]
+pub struct ThreePoint {
+    pub optimistic: i64,
+    pub likely: i64,
+    pub pessimistic: i64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -27,6 +41,13 @@ pub struct RawTask {
     pub status: String,
     pub urgency: f32,
     pub start: Option<String>,
+    pub project: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub optimistic: Option<String>,
+    pub likely: Option<String>,
+    pub pessimistic: Option<String>,
+    pub energy: Option<String>,
 }
 
 #[derive(Debug)]
@@ -48,6 +69,60 @@ pub struct TimeBlock {
     pub uid: String,
     pub summary: String,
     pub dtstamp: chrono::DateTime<Utc>,
+    // uuid of the taskwarrior task this block was scheduled for, if any
+    pub task_uuid: Option<String>,
+    // pinned by the user, the scheduler never moves it
+    pub locked: bool,
+    // uuids of the small tasks combined into this block, empty unless it is a batch
+    pub batch: Vec<String>,
+    pub location: Option<String>,
+    // false for free, cancelled, declined and (by config) tentative or all-day events
+    pub busy: bool,
+    // TRANSP, STATUS and ATTENDEE lines, kept so they are written back unchanged
+    pub availability: Vec<String>,
+    // from another profile's calendar: busy time here, never written to this one
+    pub foreign: bool,
+    // starts on a date rather than a time, and is written back that way
+    pub all_day: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Annotation {
+    pub entry: String,
+    pub description: String,
+}
+
+// a finished task as exported by taskwarrior, used to measure actual effort
+#[derive(Debug, Deserialize)]
+pub struct DoneTask {
+    pub uuid: String,
+    pub description: String,
+    pub project: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub estimated: Option<String>,
+    pub end: Option<String>,
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EffortRecord {
+    pub uuid: String,
+    pub description: String,
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+    pub estimated: i64,
+    pub actual: i64,
+    pub completed: i64,
+}
+
+// the parts of a taskwarrior export needed to tell whether a task is still open
+#[derive(Debug, Deserialize)]
+pub struct TaskState {
+    pub uuid: String,
+    pub status: String,
+    pub end: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -64,36 +139,274 @@ impl Gap {
         }
     }
 }
+/// A password or token. Its Debug output never shows the value.
+#[derive(Deserialize, Clone, Serialize, Default, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            f.write_str("\"\"")
+        } else {
+            f.write_str("<redacted>")
+        }
+    }
+}
+
 #[derive(Deserialize, Clone, Serialize, Debug)]
-pub struct Basic {
+#[serde(deny_unknown_fields)]
+pub struct Account {
     pub cal_url: String,
     pub cal_username: String,
-    pub cal_pass: String,
+    // plaintext fallback, prefer one of the options below
+    #[serde(default)]
+    pub cal_pass: Secret,
+    // a command that prints the password, such as "pass show caldav"
+    #[serde(default)]
+    pub password_command: Option<String>,
+    // a file holding only the password, readable by you alone
+    #[serde(default)]
+    pub password_file: Option<String>,
 }
-pub struct oath2 {
-    pub client_id: String,
-    pub client_secret: String,
-}
-
 #[derive(Deserialize, Clone, Serialize, Debug)]
+#[serde(deny_unknown_fields)]
 pub struct ConfigInfo {
-    pub Basic: Basic,
-    pub main: main,
+    // the schema version, see `crate::schema`
+    pub version: u32,
+    pub caldav: Account,
+    #[serde(default)]
+    pub planning: Planning,
+    #[serde(default)]
+    pub hook: Hook,
+    #[serde(default)]
+    pub estimates: Estimates,
+    #[serde(default)]
+    pub solver: Solver,
+    #[serde(default)]
+    pub reschedule: Reschedule,
+    #[serde(default)]
+    pub preferences: Vec<TimePreference>,
+    #[serde(default)]
+    pub energy: Energy,
+    #[serde(default)]
+    pub batching: Batching,
+    #[serde(default)]
+    pub travel: Travel,
+    #[serde(default)]
+    pub availability: Availability,
+    #[serde(default)]
+    pub cache: Cache,
+    // the profile this was loaded from, None for the default one
+    #[serde(skip)]
+    pub profile: Option<String>,
 }
 
 #[derive(Deserialize, Clone, Serialize, Debug)]
-pub struct main {
+#[serde(default, deny_unknown_fields)]
+pub struct Planning {
     pub days_ahead: i64,
+    // windows such as "weekdays 09:00-17:00" that every task is held to
+    pub working_hours: Vec<String>,
+    // taskwarrior filter picking this profile's tasks, such as "project:work"
+    pub filter: String,
+    // IANA zone such as "Europe/Berlin" that windows and floating times are read in, empty for the system zone
+    pub timezone: String,
+}
+impl Default for Planning {
+    fn default() -> Self {
+        Self {
+            days_ahead: 365,
+            working_hours: vec![],
+            filter: String::new(),
+            timezone: String::new(),
+        }
+    }
+}
+impl Planning {
+    pub fn zone(&self) -> crate::Result<Tz> {
+        zone(&self.timezone).map_err(|e| crate::Error::Config(format!("planning.timezone: {e}")))
+    }
+}
+#[derive(Deserialize, Clone, Serialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Hook {
+    // spawn a schedule/reschedule from the taskwarrior hook instead of only queueing it
+    pub trigger: bool,
+}
+#[derive(Deserialize, Clone, Serialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Estimates {
+    // scale `estimated` by how long similar tasks actually took before scheduling
+    pub apply_correction: bool,
+}
+#[derive(Deserialize, Clone, Serialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Solver {
+    // "greedy" or "optimal"
+    pub mode: String,
+    pub time_budget_ms: u64,
+    // how many minutes of lateness splitting a task across two gaps is worth
+    pub split_penalty_minutes: i64,
+}
+impl Default for Solver {
+    fn default() -> Self {
+        Self {
+            mode: "greedy".to_string(),
+            time_budget_ms: 2000,
+            split_penalty_minutes: 15,
+        }
+    }
+}
+#[derive(Deserialize, Clone, Serialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Reschedule {
+    // only move blocks that clash with an event instead of replanning everything
+    pub stable: bool,
+    // blocks starting within this many hours from now never move
+    pub freeze_hours: i64,
+}
+// time windows for tasks with a given tag or in a given project, written like
+// "08:00-12:00" or "weekdays 12:00-14:00"
+#[derive(Deserialize, Clone, Serialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct TimePreference {
+    pub tag: Option<String>,
+    pub project: Option<String>,
+    // soft: the task goes ahead of others while inside these windows
+    pub preferred: Vec<String>,
+    // hard: the task is only placed inside these windows
+    pub only: Vec<String>,
+    // hard: the task is never placed inside these windows
+    pub forbidden: Vec<String>,
+}
+#[derive(Deserialize, Clone, Serialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Energy {
+    // time windows of high and low energy, everything else is medium
+    pub high: Vec<String>,
+    pub low: Vec<String>,
+    // energy level for tasks with these tags and no energy UDA
+    pub tags: HashMap<String, String>,
+    // longest stretch of high energy tasks to place back to back, 0 for no limit
+    pub max_heavy_minutes: i64,
+}
+impl Default for Energy {
+    fn default() -> Self {
+        Self {
+            high: vec![],
+            low: vec![],
+            tags: HashMap::new(),
+            max_heavy_minutes: 120,
+        }
+    }
+}
+#[derive(Deserialize, Clone, Serialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Batching {
+    // how much moving straight on to another project counts against a task,
+    // on the same scale as one level of energy mismatch
+    pub switch_cost: i64,
+    // combine tasks shorter than small_minutes into one admin batch event
+    pub batch_small: bool,
+    pub small_minutes: i64,
+    // longest a single batch may get
+    pub batch_minutes: i64,
+}
+impl Default for Batching {
+    fn default() -> Self {
+        Self {
+            switch_cost: 0,
+            batch_small: false,
+            small_minutes: 15,
+            batch_minutes: 60,
+        }
+    }
+}
+#[derive(Deserialize, Clone, Serialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Travel {
+    // tags that tie a task to a place, matched against event LOCATION
+    pub places: Vec<String>,
+    // where you are when no event around a gap has a location
+    pub default_place: Option<String>,
+    // travel time between two different locations
+    pub buffer_minutes: i64,
+    // travel time for a pair of places, keyed like "home-office"
+    pub times: HashMap<String, i64>,
+}
+impl Default for Travel {
+    fn default() -> Self {
+        Self {
+            places: vec![],
+            default_place: None,
+            buffer_minutes: 15,
+            times: HashMap::new(),
+        }
+    }
+}
+#[derive(Deserialize, Clone, Serialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Availability {
+    // "busy" or "free", for tentative events and invitations you have not answered
+    pub tentative: String,
+    // "busy" or "free", for all-day events without a TRANSP property
+    pub all_day: String,
+    // your attendee address, defaults to cal_username when that is an email
+    pub email: Option<String>,
+}
+impl Default for Availability {
+    fn default() -> Self {
+        Self {
+            tentative: "busy".to_string(),
+            all_day: "free".to_string(),
+            email: None,
+        }
+    }
 }
 impl ::std::default::Default for ConfigInfo {
     fn default() -> Self {
         Self {
-            Basic: Basic {
+            version: crate::schema::VERSION,
+            caldav: Account {
                 cal_url: "your cal url".to_string(),
                 cal_username: "your cal_username".to_string(),
-                cal_pass: "your cal password".to_string(),
+                cal_pass: Secret::default(),
+                password_command: None,
+                password_file: None,
             },
-            main: main { days_ahead: 365 },
+            planning: Planning::default(),
+            hook: Hook::default(),
+            estimates: Estimates::default(),
+            solver: Solver::default(),
+            reschedule: Reschedule::default(),
+            preferences: vec![],
+            energy: Energy::default(),
+            batching: Batching::default(),
+            travel: Travel::default(),
+            availability: Availability::default(),
+            cache: Cache::default(),
+            profile: None,
         }
     }
 }
+#[derive(Deserialize, Clone, Serialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Cache {
+    // where downloaded calendars are kept, the XDG cache dir when unset
+    pub dir: Option<String>,
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+use crate::dates::Tz;
+use crate::plan::{block_end, expand_recurrences};
+use crate::preferences::Constraints;
+use crate::structs::{Gap, Task, TimeBlock, Travel};
+
+/// Where you are over a stretch of free time, if the events around it say.
+pub struct Stay {
+    pub start: i64,
+    pub end: i64,
+    pub place: Option<String>,
+}
+
+pub struct Places {
+    names: Vec<String>,
+    default_place: Option<String>,
+    buffer: i64,
+    times: HashMap<String, i64>,
+}
+
+impl Places {
+    pub fn new(config: &Travel) -> Self {
+        Self {
+            names: config.places.iter().map(|p| p.to_lowercase()).collect(),
+            default_place: config.default_place.as_ref().map(|p| p.to_lowercase()),
+            buffer: config.buffer_minutes * 60,
+            times: config
+                .times
+                .iter()
+                .map(|(pair, minutes)| (pair.to_lowercase(), minutes * 60))
+                .collect(),
+        }
+    }
+
+    // a known place named in the event location, else the location itself
+    fn place_of(&self, location: &str) -> String {
+        let location = location.trim().to_lowercase();
+        self.names
+            .iter()
+            .find(|name| location.contains(name.as_str()))
+            .cloned()
+            .unwrap_or(location)
+    }
+
+    /// The place a task is tied to through one of its tags.
+    pub fn place_of_task(&self, task: &Task) -> Option<&String> {
+        self.names
+            .iter()
+            .find(|name| task.tags.iter().any(|t| t.to_lowercase() == **name))
+    }
+
+    fn travel(&self, from: &str, to: &str) -> i64 {
+        if from == to {
+            return 0;
+        }
+        self.times
+            .get(&format!("{from}-{to}"))
+            .or_else(|| self.times.get(&format!("{to}-{from}")))
+            .copied()
+            .unwrap_or(self.buffer)
+    }
+
+    /// Keeps travel time free in every gap that leads to an event at another
+    /// place and works out where you are during each gap. You stay where the
+    /// last event was, or at `default_place` before any, until it is time to
+    /// leave; a task tied to the next place can start once you have got there.
+    /// The last stay runs on from `overflow_start` at the place of the last
+    /// event.
+    pub fn apply(
+        &self,
+        gaps: &mut Vec<Gap>,
+        blocks: &[TimeBlock],
+        overflow_start: i64,
+        zone: Tz,
+    ) -> Vec<Stay> {
+        let mut located: Vec<(i64, i64, String)> = expand_recurrences(blocks, zone)
+            .iter()
+            .filter(|b| b.busy)
+            .filter_map(|b| {
+                Some((
+                    b.dtstart,
+                    block_end(b),
+                    self.place_of(b.location.as_deref()?),
+                ))
+            })
+            .collect();
+        located.sort_by_key(|l| l.0);
+
+        let mut stays = vec![];
+        gaps.retain_mut(|gap| {
+            let here = located
+                .iter()
+                .rev()
+                .find(|l| l.1 <= gap.start)
+                .map(|l| &l.2)
+                .or(self.default_place.as_ref());
+            let there = located.iter().find(|l| l.0 >= gap.end).map(|l| &l.2);
+            let travel = match (here, there) {
+                (Some(from), Some(to)) => self.travel(from, to),
+                _ => 0,
+            };
+            // leave in time for the next event, or arrive in time to work there
+            let end = gap.end;
+            gap.end -= travel;
+            if gap.end <= gap.start {
+                return false;
+            }
+            stays.push(Stay {
+                start: gap.start,
+                end: gap.end,
+                place: here.or(there).cloned(),
+            });
+            // once there you stay until the event starts
+            if travel > 0 {
+                stays.push(Stay {
+                    start: gap.start + travel,
+                    end,
+                    place: there.cloned(),
+                });
+            }
+            true
+        });
+        stays.push(Stay {
+            start: overflow_start,
+            end: i64::MAX,
+            place: located
+                .last()
+                .map(|l| &l.2)
+                .or(self.default_place.as_ref())
+                .cloned(),
+        });
+        stays
+    }
+
+    /// Holds every task tied to a place to the stays at that place, or where
+    /// the calendar does not say where you are.
+    pub fn bind(
+        &self,
+        constraints: &mut HashMap<String, Constraints>,
+        tasks: &[Task],
+        stays: &[Stay],
+    ) {
+        for task in tasks {
+            let Some(place) = self.place_of_task(task) else {
+                continue;
+            };
+            let ranges = stays
+                .iter()
+                .filter(|s| s.place.as_ref().is_none_or(|p| p == place))
+                .map(|s| (s.start, s.end))
+                .collect();
+            constraints
+                .entry(task.uuid.clone())
+                .or_default()
+                .limit_to(ranges);
+        }
+    }
+}
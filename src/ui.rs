@@ -1,29 +1,15 @@
-use core::time;
 use std::fmt::Debug;
 use std::io;
 
-use chrono::DateTime;
-use chrono::Local;
-use chrono::TimeDelta;
-use chrono::Utc;
 use cronwave::structs::TimeBlock;
 use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
-use ratatui::layout::{self, Constraint, Layout};
-use ratatui::widgets::calendar::Monthly;
+use ratatui::layout::{Constraint, Layout};
 use ratatui::widgets::{Row, Table, TableState};
-use ratatui::{
-    buffer::Buffer,
-    layout::Rect,
-    style::Stylize,
-    symbols::border,
-    text::{Line, Text},
-    widgets::{Block, Cell, Paragraph, Widget},
-    DefaultTerminal, Frame,
-};
 use ratatui::{
     style::{Color, Style},
     widgets::Borders,
 };
+use ratatui::{text::Line, widgets::Block, DefaultTerminal, Frame};
 
 #[derive(Debug)]
 enum Focus {
@@ -53,6 +39,7 @@ impl Focus {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
 struct Cal {
     cols: usize,
@@ -184,10 +171,7 @@ impl Cal {
         let brendan = focused_block("bottom", matches!(self.focus, Focus::Bottom));
         let mut rows = Vec::new();
         let header = Row::new(vec!["Summary", "Start", "End"]);
-        let timeline = Local::now().timestamp();
         for event in &self.events {
-            let time = DateTime::from_timestamp(event.dtstart, 0).unwrap();
-
             rows.push(Row::new(vec![
                 event.summary.clone(),
                 chrono::DateTime::from_timestamp(event.dtstart, 0)
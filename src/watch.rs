@@ -0,0 +1,102 @@
+use chrono::Utc;
+use serde_json::Value;
+
+use crate::dates::parse_datetime_in;
+use crate::duration::parse_duration;
+
+// the fields that change where a task ends up on the calendar
+const WATCHED_FIELDS: [&str; 4] = ["due", "estimated", "status", "depends"];
+
+/// The reason queued for a task that needs placing rather than replanning.
+pub const ADDED: &str = "add";
+
+/// What a taskwarrior hook does with a task it was handed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Decision {
+    /// Tag the task `+unscheduled`.
+    pub tag_unscheduled: bool,
+    /// Queue a replan, with [`ADDED`] or the fields that changed as the reason.
+    pub queue: Option<String>,
+    /// Shown to the user by taskwarrior.
+    pub feedback: Option<String>,
+}
+
+/// Decides what the on-add (`original` is None) or on-modify hook does with
+/// `task`. An error is the message to reject the change with.
+pub fn decide(original: Option<&Value>, task: &Value) -> Result<Decision, String> {
+    // finishing a task must always work, even one whose estimate or due no longer parses
+    let finishing = matches!(task["status"].as_str(), Some("completed" | "deleted"));
+    if !finishing {
+        validate(task)?;
+    }
+    let Some(original) = original else {
+        if task.get("estimated").is_some() && !has_tag(task, "scheduled") {
+            return Ok(added());
+        }
+        return Ok(Decision::default());
+    };
+    // an estimate added later is treated like a new task with one
+    let estimate_added =
+        original.get("estimated").is_none() && task.get("estimated").is_some() && !finishing;
+    if estimate_added && !has_tag(task, "scheduled") && !has_tag(task, "unscheduled") {
+        return Ok(added());
+    }
+    let changed: Vec<&str> = WATCHED_FIELDS
+        .into_iter()
+        .filter(|field| original.get(field) != task.get(field))
+        .collect();
+    let tracked = has_tag(task, "scheduled") || has_tag(task, "unscheduled");
+    if !tracked || changed.is_empty() {
+        return Ok(Decision::default());
+    }
+    Ok(Decision {
+        tag_unscheduled: false,
+        queue: Some(changed.join(",")),
+        feedback: Some(format!(
+            "cronwave: {} changed, reschedule queued",
+            changed.join(", ")
+        )),
+    })
+}
+
+fn added() -> Decision {
+    Decision {
+        tag_unscheduled: true,
+        queue: Some(ADDED.to_string()),
+        feedback: Some("cronwave: task queued for scheduling".to_string()),
+    }
+}
+
+fn validate(task: &Value) -> Result<(), String> {
+    let Some(estimated) = task.get("estimated") else {
+        return Ok(());
+    };
+    let description = task["description"].as_str().unwrap_or("<no description>");
+    let Some(estimated) = estimated.as_str() else {
+        return Err(format!(
+            "cronwave: estimated on task '{description}' must be a duration string"
+        ));
+    };
+    if let Err(e) = parse_duration(estimated) {
+        return Err(format!(
+            "cronwave: invalid estimated on task '{description}': {e}"
+        ));
+    }
+    let Some(due) = task["due"].as_str() else {
+        return Err(format!(
+            "cronwave: task '{description}' has an estimate but no due date"
+        ));
+    };
+    if let Err(e) = parse_datetime_in(due, &Utc) {
+        return Err(format!(
+            "cronwave: invalid due on task '{description}': {e}"
+        ));
+    }
+    Ok(())
+}
+
+fn has_tag(task: &Value, tag: &str) -> bool {
+    task["tags"]
+        .as_array()
+        .is_some_and(|tags| tags.iter().any(|t| t == tag))
+}
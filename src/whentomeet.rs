@@ -1,11 +1,13 @@
-use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono::{DateTime, Local, NaiveDate, TimeZone};
+use cronwave::availability::free_between;
 use cronwave::structs::*;
 use regex::Regex;
 use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, COOKIE, ORIGIN, REFERER, USER_AGENT};
 use reqwest::Client;
-use rrule::RRuleSet;
 use std::collections::HashMap;
-use std::error::Error;
+
+use cronwave::dates::Tz;
+use cronwave::plan::{block_end, expand_recurrences};
 async fn get_times(url: String) -> (Vec<String>, usize, i64, i64) {
     let html = reqwest::get(url).await.unwrap().text().await.unwrap();
 
@@ -24,7 +26,7 @@ async fn get_times(url: String) -> (Vec<String>, usize, i64, i64) {
         let day = time.date_naive();
         times_map.entry(day).or_default().push(time.timestamp());
     }
-    let first_day_blocks_num = if let Some((_day, mut dts)) = times_map.iter_mut().next() {
+    let first_day_blocks_num = if let Some((_day, dts)) = times_map.iter_mut().next() {
         dts.sort();
         let start = *dts.first().unwrap();
         let end = *dts.last().unwrap();
@@ -61,66 +63,6 @@ async fn get_times(url: String) -> (Vec<String>, usize, i64, i64) {
         end_of_last_day,
     )
 }
-fn find_the_gaps(blocks: &mut Vec<TimeBlock>) -> Vec<Gap> {
-    let mut gap_vec = vec![];
-
-    // Expand recurrences into actual blocks first
-    let mut expanded_blocks: Vec<TimeBlock> = vec![];
-    for block in blocks {
-        if let Some(rrule) = block.rrule.clone() {
-            // Use block.dtstart as the seed
-            let tz = rrule::Tz::Local(Local);
-            let start_tz = Local
-                .timestamp_opt(block.dtstart, 0)
-                .unwrap()
-                .with_timezone(&tz);
-
-            let rruleset = RRuleSet::new(start_tz).rrule(rrule);
-
-            for time in rruleset.all_unchecked() {
-                let mut b = block.clone();
-                b.dtstart = time.timestamp();
-                // shift dtend if duration is defined
-                if let Some(dur) = b.duration {
-                    b.dtend = Some(b.dtstart + dur);
-                }
-                expanded_blocks.push(b);
-            }
-        } else {
-            expanded_blocks.push(block.clone());
-        }
-    }
-
-    expanded_blocks.sort_by_key(|b| b.dtstart);
-    let now = Local::now().timestamp();
-    if let Some(first) = expanded_blocks.first() {
-        if now < first.dtstart {
-            gap_vec.push(Gap {
-                start: now,
-                end: first.dtstart,
-            });
-        }
-    }
-
-    // Walk through and find gaps between consecutive blocks
-    for w in expanded_blocks.windows(2) {
-        let current = &w[0];
-        let next = &w[1];
-
-        let current_end = match current.duration {
-            Some(dur) => current.dtstart + dur,
-            None => current.dtend.unwrap_or(current.dtstart),
-        };
-
-        if current_end < next.dtstart {
-            gap_vec.push(Gap {
-                start: current_end,
-                end: next.dtstart,
-            });
-        }
-    }
-    gap_vec
-}
 fn get_blocks(mut startday: i64, mut endday: i64, blocks_per_day: usize) -> Vec<Gap> {
     let mut gap_vec = vec![];
 
@@ -151,20 +93,27 @@ fn get_blocks(mut startday: i64, mut endday: i64, blocks_per_day: usize) -> Vec<
 }
 
 fn find_times_availaible(
-    mut blocks: Vec<TimeBlock>,
+    blocks: Vec<TimeBlock>,
     mut avail: Vec<String>,
     startday: i64,
     endday: i64,
     blocks_per_day: usize,
 ) -> String {
-    let gaps = find_the_gaps(&mut blocks);
+    // when2meet shows its grid in the browser's time zone
+    let busy = expand_recurrences(&blocks, Tz::LOCAL)
+        .iter()
+        .filter(|b| b.busy)
+        .map(|b| (b.dtstart, block_end(b)))
+        .collect::<Vec<_>>();
     let slots = get_blocks(startday, endday, blocks_per_day);
+    let until = slots.last().map(|s| s.end).unwrap_or(endday);
+    let gaps = free_between(busy, startday, until);
     let mut indexes = vec![];
-    for gap in gaps.iter().filter(|x| x.start > startday) {
+    for gap in &gaps {
         let slots_avail: Vec<usize> = slots
             .iter()
             .enumerate()
-            .filter(|(idx, slot)| slot.start >= gap.start && slot.end <= gap.end)
+            .filter(|(_idx, slot)| slot.start >= gap.start && slot.end <= gap.end)
             .map(|(idx, _slot)| idx)
             .collect();
 
@@ -181,7 +130,7 @@ pub async fn meet(
     name: String,
     pass: String,
     blocks: Vec<TimeBlock>,
-) -> Result<(), Box<dyn Error>> {
+) -> color_eyre::Result<()> {
     let client = Client::new();
     let re = Regex::new(r"\?(.*?)\-").unwrap();
     let mut id = String::new();
@@ -256,7 +205,7 @@ pub async fn post_times(
     event_id: String,
     person_id: String,
     availibility: String,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> color_eyre::Result<()> {
     let client = Client::new();
 
     // Headers
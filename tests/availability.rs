@@ -0,0 +1,161 @@
+use cronwave::availability::{
+    availability_lines, free_between, is_busy, merge_busy, property_line,
+};
+use cronwave::structs::{Availability, ConfigInfo};
+use icalendar::{Calendar, CalendarComponent, Event};
+use proptest::prelude::*;
+
+fn intervals() -> impl Strategy<Value = Vec<(i64, i64)>> {
+    prop::collection::vec((0i64..10_000, 0i64..2_000), 0..40)
+        .prop_map(|v| v.into_iter().map(|(s, len)| (s, s + len)).collect())
+}
+
+fn busy_at(busy: &[(i64, i64)], t: i64) -> bool {
+    busy.iter().any(|(s, e)| *s <= t && t < *e)
+}
+
+#[test]
+fn contained_event_does_not_open_a_gap() {
+    let gaps = free_between([(0, 100), (10, 20)], 0, 200);
+    assert_eq!(gaps.len(), 1);
+    assert_eq!((gaps[0].start, gaps[0].end), (100, 200));
+}
+
+#[test]
+fn gaps_before_and_after_the_events() {
+    let gaps = free_between([(50, 60)], 0, 100);
+    let gaps: Vec<(i64, i64)> = gaps.iter().map(|g| (g.start, g.end)).collect();
+    assert_eq!(gaps, vec![(0, 50), (60, 100)]);
+}
+
+#[test]
+fn no_events_is_all_free() {
+    let gaps = free_between([], 10, 20);
+    assert_eq!(gaps.len(), 1);
+    assert_eq!((gaps[0].start, gaps[0].end), (10, 20));
+}
+
+#[test]
+fn touching_events_merge() {
+    assert_eq!(merge_busy([(0, 10), (10, 20), (30, 30)]), vec![(0, 20)]);
+}
+
+proptest! {
+    #[test]
+    fn merged_is_sorted_and_disjoint(busy in intervals()) {
+        let merged = merge_busy(busy.clone());
+        for w in merged.windows(2) {
+            prop_assert!(w[0].1 < w[1].0);
+        }
+        for t in (0..12_000).step_by(37) {
+            prop_assert_eq!(busy_at(&busy, t), busy_at(&merged, t));
+        }
+    }
+
+    #[test]
+    fn merge_ignores_order(mut busy in intervals()) {
+        let merged = merge_busy(busy.clone());
+        busy.reverse();
+        prop_assert_eq!(merge_busy(busy), merged);
+    }
+
+    #[test]
+    fn gaps_are_exactly_the_free_time(busy in intervals(), from in 0i64..5_000, len in 0i64..8_000) {
+        let until = from + len;
+        let gaps = free_between(busy.clone(), from, until);
+        for gap in &gaps {
+            prop_assert!(from <= gap.start && gap.start < gap.end && gap.end <= until);
+        }
+        for w in gaps.windows(2) {
+            // gaps never touch, otherwise they would be one gap
+            prop_assert!(w[0].end < w[1].start);
+        }
+        for t in from..until {
+            let free = gaps.iter().any(|g| g.start <= t && t < g.end);
+            prop_assert_eq!(free, !busy_at(&busy, t));
+        }
+    }
+}
+
+// the first VEVENT of a calendar built around the given lines
+fn event(lines: &str) -> Event {
+    let text = format!(
+        "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:a\r\n{lines}END:VEVENT\r\nEND:VCALENDAR\r\n"
+    );
+    let calendar: Calendar = text.parse().unwrap();
+    calendar
+        .components
+        .into_iter()
+        .find_map(|c| match c {
+            CalendarComponent::Event(event) => Some(event),
+            _ => None,
+        })
+        .unwrap()
+}
+
+fn config(tentative: &str, all_day: &str) -> ConfigInfo {
+    ConfigInfo {
+        availability: Availability {
+            tentative: tentative.to_string(),
+            all_day: all_day.to_string(),
+            email: Some("me@example.com".to_string()),
+        },
+        ..ConfigInfo::default()
+    }
+}
+
+#[test]
+fn transparent_events_are_free() {
+    let config = config("busy", "busy");
+    assert!(!is_busy(&event("TRANSP:TRANSPARENT\r\n"), false, &config));
+    assert!(!is_busy(&event("TRANSP:TRANSPARENT\r\n"), true, &config));
+    assert!(is_busy(&event("TRANSP:OPAQUE\r\n"), false, &config));
+    assert!(is_busy(&event(""), false, &config));
+}
+
+#[test]
+fn tentative_follows_the_config() {
+    let tentative = event("STATUS:TENTATIVE\r\n");
+    let unanswered = event("ATTENDEE;PARTSTAT=NEEDS-ACTION:mailto:me@example.com\r\n");
+    let declined = event("ATTENDEE;PARTSTAT=DECLINED:mailto:Me@Example.com\r\n");
+    for (setting, busy) in [("busy", true), ("free", false)] {
+        let config = config(setting, "free");
+        assert_eq!(is_busy(&tentative, false, &config), busy);
+        assert_eq!(is_busy(&unanswered, false, &config), busy);
+        assert!(!is_busy(&declined, false, &config));
+    }
+    assert!(!is_busy(
+        &event("STATUS:CANCELLED\r\n"),
+        false,
+        &config("busy", "busy")
+    ));
+}
+
+#[test]
+fn all_day_follows_the_config_unless_it_says() {
+    assert!(!is_busy(&event(""), true, &config("busy", "free")));
+    assert!(is_busy(&event(""), true, &config("busy", "busy")));
+    // an explicit TRANSP wins over the all-day setting
+    assert!(is_busy(
+        &event("TRANSP:OPAQUE\r\n"),
+        true,
+        &config("busy", "free")
+    ));
+}
+
+#[test]
+fn param_values_with_separators_are_quoted() {
+    let attendee = event("ATTENDEE;CN=\"Doe, Jane\";PARTSTAT=ACCEPTED:mailto:jane@example.com\r\n");
+    let lines = availability_lines(&attendee);
+    assert_eq!(lines.len(), 1);
+    assert!(lines[0].contains(";CN=\"Doe, Jane\""), "{}", lines[0]);
+    assert!(lines[0].contains(";PARTSTAT=ACCEPTED"), "{}", lines[0]);
+    assert!(lines[0].ends_with(":mailto:jane@example.com"));
+    // the line reads back to the same property
+    let again = event(&format!("{}\r\n", lines[0]));
+    assert_eq!(availability_lines(&again), lines);
+    assert_eq!(
+        property_line(&icalendar::Property::new("TRANSP", "OPAQUE")),
+        "TRANSP:OPAQUE"
+    );
+}
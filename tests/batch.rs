@@ -0,0 +1,128 @@
+use cronwave::batch::combine;
+use cronwave::plan::{block_end, plan, reschedule};
+use cronwave::structs::{Batching, ConfigInfo, Task, TimeBlock, TimePreference};
+
+mod common;
+use common::{event, monday_nine, task, HOUR, MINUTE};
+
+fn batching() -> Batching {
+    Batching {
+        batch_small: true,
+        ..Batching::default()
+    }
+}
+
+fn small_tasks() -> Vec<Task> {
+    let now = monday_nine();
+    vec![
+        task("a", 10 * MINUTE, now + HOUR),
+        task("b", 10 * MINUTE, now + 2 * HOUR),
+        task("c", 10 * MINUTE, now + 3 * HOUR),
+    ]
+}
+
+#[test]
+fn batch_uid_depends_only_on_the_members() {
+    let (placed, batches) = combine(small_tasks(), &batching(), |_| ());
+    assert_eq!(placed.len(), 1);
+    let uid = &placed[0].uuid;
+    assert!(uid.starts_with("cronwave-batch-"), "{uid}");
+    assert_eq!(batches[uid], vec!["a", "b", "c"]);
+
+    let mut reversed = small_tasks();
+    reversed.reverse();
+    reversed[0].due += 10 * HOUR;
+    let (again, _) = combine(reversed, &batching(), |_| ());
+    assert_eq!(&again[0].uuid, uid);
+}
+
+#[test]
+fn tasks_with_other_rules_are_not_batched_together() {
+    let mut tasks = small_tasks();
+    tasks[1].tags = vec!["errand".to_string()];
+    let (placed, batches) = combine(tasks, &batching(), |t| t.tags.clone());
+    // a and c share a batch, b is left on its own
+    assert_eq!(placed.len(), 2);
+    assert_eq!(batches.len(), 1);
+    assert!(placed.iter().any(|t| t.uuid == "b"));
+}
+
+#[test]
+fn members_keep_their_windows() {
+    let now = monday_nine();
+    let mut tasks = small_tasks();
+    for t in &mut tasks[1..] {
+        t.tags = vec!["errand".to_string()];
+    }
+    let mut config = ConfigInfo {
+        batching: batching(),
+        ..ConfigInfo::default()
+    };
+    config.planning.timezone = "UTC".to_string();
+    config.preferences = vec![TimePreference {
+        tag: Some("errand".to_string()),
+        only: vec!["12:00-13:00".to_string()],
+        ..TimePreference::default()
+    }];
+    let schedule = plan(tasks, vec![], &config, now).unwrap();
+    let errands = schedule
+        .placed
+        .iter()
+        .find(|b| b.batch.contains(&"b".to_string()))
+        .unwrap();
+    assert_eq!(errands.batch, vec!["b", "c"]);
+    assert_eq!(errands.dtstart, now + 3 * HOUR);
+    let a = schedule.placed.iter().find(|b| b.uid == "a").unwrap();
+    assert_eq!(a.dtstart, now);
+}
+
+#[test]
+fn placed_tasks_are_the_members_not_the_batch() {
+    let config = ConfigInfo {
+        batching: batching(),
+        ..ConfigInfo::default()
+    };
+    let schedule = plan(small_tasks(), vec![], &config, monday_nine()).unwrap();
+    assert!(schedule.placed.iter().any(|b| !b.batch.is_empty()));
+    assert_eq!(schedule.placed_tasks(), vec!["a", "b", "c"]);
+}
+
+#[test]
+fn reschedule_counts_a_batch_once_less_its_kept_pieces() {
+    let now = monday_nine();
+    let tasks = small_tasks();
+    let mut config = ConfigInfo {
+        batching: batching(),
+        ..ConfigInfo::default()
+    };
+    config.reschedule.freeze_hours = 1;
+    let (placed, _) = combine(tasks.clone(), &config.batching, |_| ());
+    let uid = placed[0].uuid.clone();
+    let piece = |start: i64, end: i64| TimeBlock {
+        task_uuid: Some(uid.clone()),
+        batch: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+        ..event(&uid, start, end)
+    };
+    let blocks = vec![
+        // frozen, holds a and half of b
+        piece(now + 40 * MINUTE, now + HOUR - 5 * MINUTE),
+        // two pieces let go, the batch must still only come back once
+        piece(now + 2 * HOUR, now + 2 * HOUR + 2 * MINUTE),
+        piece(now + 3 * HOUR, now + 3 * HOUR + 3 * MINUTE),
+        event("meeting", now + 2 * HOUR, now + 4 * HOUR),
+    ];
+    let done = reschedule(blocks, &tasks, &config, now).unwrap();
+    assert_eq!(done.kept.len(), 1);
+    let replanned: i64 = done
+        .schedule
+        .placed
+        .iter()
+        .map(|b| block_end(b) - b.dtstart)
+        .sum();
+    assert_eq!(replanned, 30 * MINUTE - 15 * MINUTE);
+    assert!(done
+        .schedule
+        .placed
+        .iter()
+        .all(|b| b.uid != "a" && !b.batch.contains(&"a".to_string())));
+}
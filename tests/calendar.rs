@@ -0,0 +1,67 @@
+use cronwave::calendar::{parse_event, write_calendar};
+use cronwave::structs::{ConfigInfo, TimeBlock};
+use icalendar::{Calendar, CalendarComponent};
+
+fn config() -> ConfigInfo {
+    let mut config = ConfigInfo::default();
+    config.planning.timezone = "Europe/Berlin".to_string();
+    config
+}
+
+fn parse(text: &str) -> Vec<TimeBlock> {
+    let calendar: Calendar = text.parse().unwrap();
+    calendar
+        .components
+        .iter()
+        .filter_map(|c| match c {
+            CalendarComponent::Event(event) => Some(parse_event(event, &config()).unwrap()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn round_trip(text: &str) -> (String, Vec<TimeBlock>) {
+    let zone = config().planning.zone().unwrap();
+    let written = write_calendar(&parse(text), zone);
+    let again = parse(&written);
+    (written, again)
+}
+
+const ALL_DAY: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VEVENT\r\nUID:holiday\r\nSUMMARY:holiday\r\nDTSTAMP:20250801T000000Z\r\nDTSTART;VALUE=DATE:20250901\r\nDTEND;VALUE=DATE:20250902\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+#[test]
+fn all_day_event_stays_all_day() {
+    let first = parse(ALL_DAY);
+    assert!(first[0].all_day);
+    // all-day events are free unless the config says otherwise
+    assert!(!first[0].busy);
+
+    let (written, again) = round_trip(ALL_DAY);
+    assert!(written.contains("DTSTART;VALUE=DATE:20250901"), "{written}");
+    assert!(written.contains("DTEND;VALUE=DATE:20250902"), "{written}");
+    assert!(again[0].all_day);
+    assert!(!again[0].busy);
+    assert_eq!(again[0].dtstart, first[0].dtstart);
+    assert_eq!(again[0].dtend, first[0].dtend);
+}
+
+#[test]
+fn all_day_duration_is_in_days() {
+    let text = ALL_DAY.replace("DTEND;VALUE=DATE:20250902", "DURATION:P2D");
+    let (written, again) = round_trip(&text);
+    assert!(written.contains("DURATION:P2D"), "{written}");
+    assert!(again[0].all_day);
+    assert_eq!(again[0].duration, Some(2 * 86_400));
+}
+
+#[test]
+fn timed_event_keeps_its_time() {
+    let text = ALL_DAY
+        .replace("DTSTART;VALUE=DATE:20250901", "DTSTART:20250901T090000")
+        .replace("DTEND;VALUE=DATE:20250902", "DTEND:20250901T100000");
+    let (written, again) = round_trip(&text);
+    assert!(written.contains("DTSTART:20250901T090000"), "{written}");
+    assert!(!again[0].all_day);
+    assert!(again[0].busy);
+    assert_eq!(again[0].dtstart, parse(&text)[0].dtstart);
+}
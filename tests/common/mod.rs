@@ -0,0 +1,61 @@
+// helpers shared by the test files, each of which uses only some of them
+#![allow(dead_code)]
+
+use chrono::{TimeZone, Utc};
+use cronwave::structs::{Task, TimeBlock};
+
+pub const MINUTE: i64 = 60;
+pub const HOUR: i64 = 3600;
+
+// Monday 2025-09-01 09:00 UTC
+pub fn monday_nine() -> i64 {
+    Utc.with_ymd_and_hms(2025, 9, 1, 9, 0, 0)
+        .unwrap()
+        .timestamp()
+}
+
+pub fn task(uuid: &str, estimated: i64, due: i64) -> Task {
+    Task {
+        id: 0,
+        uuid: uuid.to_string(),
+        description: uuid.to_string(),
+        due,
+        estimated,
+        status: "pending".to_string(),
+        urgency: 0.0,
+        start: None,
+        project: None,
+        tags: vec![],
+        three_point: None,
+        energy: None,
+    }
+}
+
+// a plain busy event, not tied to any task
+pub fn event(uid: &str, start: i64, end: i64) -> TimeBlock {
+    TimeBlock {
+        rrule: None,
+        dtstart: start,
+        duration: Some(end - start),
+        dtend: None,
+        uid: uid.to_string(),
+        summary: uid.to_string(),
+        dtstamp: Utc::now(),
+        task_uuid: None,
+        locked: false,
+        batch: vec![],
+        location: None,
+        busy: true,
+        availability: vec![],
+        foreign: false,
+        all_day: false,
+    }
+}
+
+// a block scheduled for the task `uuid`
+pub fn task_block(uuid: &str, start: i64, end: i64) -> TimeBlock {
+    TimeBlock {
+        task_uuid: Some(uuid.to_string()),
+        ..event(uuid, start, end)
+    }
+}
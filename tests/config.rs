@@ -0,0 +1,168 @@
+use cronwave::schema::{migrate_text, parse, resolve, validate, Source, VERSION};
+use cronwave::Error;
+
+const V1: &str = r#"
+[Basic]
+cal_url = "https://cal.example.com/me/"
+cal_username = "me"
+
+[main]
+days_ahead = 30
+
+[solver]
+mode = "optimal"
+"#;
+
+fn message(error: Error) -> String {
+    match error {
+        Error::Config(message) => message,
+        other => panic!("expected a config error, got {other}"),
+    }
+}
+
+#[test]
+fn version_one_is_migrated() {
+    let (from, text) = migrate_text(V1).unwrap().unwrap();
+    assert_eq!(from, 1);
+    let config = parse(&text).unwrap();
+    assert_eq!(config.version, VERSION);
+    assert_eq!(config.caldav.cal_url, "https://cal.example.com/me/");
+    assert_eq!(config.planning.days_ahead, 30);
+    assert_eq!(config.solver.mode, "optimal");
+    validate(&config).unwrap();
+}
+
+#[test]
+fn current_version_is_left_alone() {
+    let (_, text) = migrate_text(V1).unwrap().unwrap();
+    assert!(migrate_text(&text).unwrap().is_none());
+}
+
+#[test]
+fn newer_version_is_refused() {
+    let text = format!("version = {}\n", VERSION + 1);
+    assert!(message(migrate_text(&text).unwrap_err()).starts_with("version:"));
+}
+
+#[test]
+fn missing_sections_use_defaults() {
+    let config = parse(
+        r#"
+version = 2
+
+[caldav]
+cal_url = "https://cal.example.com/me/"
+cal_username = "me"
+"#,
+    )
+    .unwrap();
+    assert_eq!(config.planning.days_ahead, 365);
+    assert_eq!(config.solver.mode, "greedy");
+}
+
+#[test]
+fn unknown_key_is_named() {
+    let (_, text) = migrate_text(V1).unwrap().unwrap();
+    let text = text.replace("days_ahead", "days_ahed");
+    let message = message(parse(&text).unwrap_err());
+    assert!(message.contains("days_ahed"), "{message}");
+}
+
+#[test]
+fn bad_window_names_its_key() {
+    let (_, text) = migrate_text(V1).unwrap().unwrap();
+    let mut config = parse(&text).unwrap();
+    config.planning.working_hours = vec!["9am-5pm".to_string()];
+    let message = message(validate(&config).unwrap_err());
+    assert!(message.starts_with("planning.working_hours:"), "{message}");
+}
+
+fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+#[test]
+fn flags_beat_env_beat_file() {
+    let (_, text) = migrate_text(V1).unwrap().unwrap();
+    let resolved = resolve(
+        &text,
+        &env(&[
+            ("CRONWAVE_PLANNING_DAYS_AHEAD", "14"),
+            ("CRONWAVE_CALDAV_CAL_URL", "https://other.example.com/"),
+        ]),
+        &["planning.days_ahead=7".to_string()],
+    )
+    .unwrap();
+    assert_eq!(resolved.config.planning.days_ahead, 7);
+    assert_eq!(resolved.config.caldav.cal_url, "https://other.example.com/");
+    assert_eq!(resolved.config.solver.mode, "optimal");
+    let source = |key: &str| {
+        resolved
+            .values
+            .iter()
+            .find(|(k, _, _)| k == key)
+            .map(|(_, _, source)| source.clone())
+            .unwrap()
+    };
+    assert_eq!(source("planning.days_ahead"), Source::Flag);
+    assert_eq!(
+        source("caldav.cal_url"),
+        Source::Env("CRONWAVE_CALDAV_CAL_URL".to_string())
+    );
+    assert_eq!(source("solver.mode"), Source::File);
+    assert_eq!(source("hook.trigger"), Source::Default);
+}
+
+#[test]
+fn overrides_take_the_type_of_the_key() {
+    let (_, text) = migrate_text(V1).unwrap().unwrap();
+    let resolved = resolve(
+        &text,
+        &env(&[(
+            "CRONWAVE_PLANNING_WORKING_HOURS",
+            r#"["weekdays 09:00-17:00"]"#,
+        )]),
+        &[
+            "hook.trigger=true".to_string(),
+            "travel.default_place=home".to_string(),
+        ],
+    )
+    .unwrap();
+    assert_eq!(
+        resolved.config.planning.working_hours,
+        vec!["weekdays 09:00-17:00"]
+    );
+    assert!(resolved.config.hook.trigger);
+    assert_eq!(
+        resolved.config.travel.default_place.as_deref(),
+        Some("home")
+    );
+
+    let message =
+        message(resolve(&text, &[], &["planning.days_ahead=soon".to_string()]).unwrap_err());
+    assert!(message.starts_with("planning.days_ahead:"), "{message}");
+}
+
+#[test]
+fn unknown_override_names_where_it_came_from() {
+    let (_, text) = migrate_text(V1).unwrap().unwrap();
+    let message =
+        message(resolve(&text, &env(&[("CRONWAVE_PLANNING_DAYS", "3")]), &[]).unwrap_err());
+    assert!(message.contains("CRONWAVE_PLANNING_DAYS"), "{message}");
+}
+
+#[test]
+fn optimal_refuses_what_it_ignores() {
+    let (_, text) = migrate_text(V1).unwrap().unwrap();
+    let mut config = parse(&text).unwrap();
+    config.energy.high = vec!["08:00-11:00".to_string()];
+    assert!(message(validate(&config).unwrap_err()).starts_with("solver.mode:"));
+    config.energy.high.clear();
+    config.batching.switch_cost = 1;
+    assert!(message(validate(&config).unwrap_err()).starts_with("solver.mode:"));
+    config.batching.switch_cost = 0;
+    validate(&config).unwrap();
+}
@@ -0,0 +1,93 @@
+use cronwave::correction::{correct, factor, ratio};
+use cronwave::structs::{EffortRecord, Task};
+
+const HOUR: i64 = 3600;
+
+fn record(project: Option<&str>, tags: &[&str], estimated: i64, actual: i64) -> EffortRecord {
+    EffortRecord {
+        uuid: format!("{project:?}{tags:?}{estimated}{actual}"),
+        description: String::new(),
+        project: project.map(str::to_string),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        estimated,
+        actual,
+        completed: 0,
+    }
+}
+
+fn task(project: Option<&str>, tags: &[&str], estimated: i64) -> Task {
+    Task {
+        id: 0,
+        uuid: "t".to_string(),
+        description: "t".to_string(),
+        due: 0,
+        estimated,
+        status: "pending".to_string(),
+        urgency: 0.0,
+        start: None,
+        project: project.map(str::to_string),
+        tags: tags.iter().map(|t| t.to_string()).collect(),
+        three_point: None,
+        energy: None,
+    }
+}
+
+#[test]
+fn ratio_needs_enough_samples() {
+    let two = vec![record(None, &[], HOUR, 2 * HOUR); 2];
+    assert_eq!(ratio(two.iter()), None);
+    let three = vec![record(None, &[], HOUR, 2 * HOUR); 3];
+    assert_eq!(ratio(three.iter()), Some(2.0));
+}
+
+#[test]
+fn ratio_is_clamped() {
+    let slow = vec![record(None, &[], HOUR, 10 * HOUR); 3];
+    assert_eq!(ratio(slow.iter()), Some(4.0));
+    let fast = vec![record(None, &[], 10 * HOUR, HOUR); 3];
+    assert_eq!(ratio(fast.iter()), Some(0.25));
+}
+
+#[test]
+fn ratio_weighs_by_time() {
+    let records = [
+        record(None, &[], HOUR, HOUR),
+        record(None, &[], HOUR, HOUR),
+        record(None, &[], 2 * HOUR, 5 * HOUR),
+    ];
+    assert_eq!(ratio(records.iter()), Some(7.0 / 4.0));
+}
+
+#[test]
+fn project_and_tag_ratios_are_averaged() {
+    let mut records = vec![record(Some("work"), &[], HOUR, 2 * HOUR); 3];
+    records.extend(vec![record(None, &["email"], HOUR, HOUR); 3]);
+    assert_eq!(
+        factor(&task(Some("work"), &["email"], HOUR), &records),
+        Some(1.5)
+    );
+    assert_eq!(factor(&task(Some("work"), &[], HOUR), &records), Some(2.0));
+    assert_eq!(factor(&task(None, &["email"], HOUR), &records), Some(1.0));
+}
+
+#[test]
+fn falls_back_to_every_record() {
+    let mut records = vec![record(Some("work"), &[], HOUR, 2 * HOUR); 3];
+    records.push(record(Some("home"), &[], HOUR, 2 * HOUR));
+    // home alone has too few samples
+    assert_eq!(factor(&task(Some("home"), &[], HOUR), &records), Some(2.0));
+    assert_eq!(factor(&task(None, &[], HOUR), &[]), None);
+}
+
+#[test]
+fn correct_scales_estimates() {
+    let records = vec![record(Some("work"), &["deep"], HOUR, 3 * HOUR / 2); 3];
+    let mut tasks = vec![
+        task(Some("work"), &["deep"], 2 * HOUR),
+        task(None, &[], HOUR),
+    ];
+    correct(&mut tasks, &records);
+    assert_eq!(tasks[0].estimated, 3 * HOUR);
+    // nothing but the overall ratio applies to the second task
+    assert_eq!(tasks[1].estimated, 3 * HOUR / 2);
+}
@@ -0,0 +1,92 @@
+use cronwave::credentials::resolve_password_from;
+use cronwave::structs::{Account, ConfigInfo, Secret};
+use cronwave::Error;
+use std::path::PathBuf;
+
+fn account() -> Account {
+    Account {
+        cal_pass: Secret::new("from-config"),
+        ..ConfigInfo::default().caldav
+    }
+}
+
+// a password file private to this test, with the given permissions
+fn password_file(name: &str, mode: u32) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("cronwave-{}-{name}", std::process::id()));
+    std::fs::write(&path, "from-file\nignored\n").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+    path
+}
+
+fn resolve(account: &Account, env: Option<&str>) -> String {
+    resolve_password_from(account, env.map(str::to_string))
+        .unwrap()
+        .expose()
+        .to_string()
+}
+
+#[test]
+fn sources_are_tried_in_order() {
+    let file = password_file("order", 0o600);
+    let mut account = account();
+    assert_eq!(resolve(&account, None), "from-config");
+
+    account.password_file = Some(file.display().to_string());
+    assert_eq!(resolve(&account, None), "from-file");
+
+    account.password_command = Some("printf 'from-command\\nignored'".to_string());
+    assert_eq!(resolve(&account, None), "from-command");
+
+    assert_eq!(resolve(&account, Some("from-env")), "from-env");
+    std::fs::remove_file(file).unwrap();
+}
+
+#[test]
+fn failing_command_is_an_error() {
+    let account = Account {
+        password_command: Some("exit 3".to_string()),
+        ..account()
+    };
+    let result = resolve_password_from(&account, None);
+    assert!(matches!(result, Err(Error::Config(_))), "{result:?}");
+}
+
+#[test]
+fn no_password_anywhere_is_an_error() {
+    let account = Account {
+        cal_pass: Secret::default(),
+        ..account()
+    };
+    let result = resolve_password_from(&account, None);
+    assert!(matches!(result, Err(Error::Config(_))), "{result:?}");
+}
+
+#[cfg(unix)]
+#[test]
+fn readable_password_file_is_refused() {
+    let file = password_file("readable", 0o644);
+    let account = Account {
+        password_file: Some(file.display().to_string()),
+        ..account()
+    };
+    let result = resolve_password_from(&account, None);
+    std::fs::remove_file(file).unwrap();
+    match result {
+        Err(Error::Config(message)) => assert!(message.contains("chmod 600"), "{message}"),
+        other => panic!("expected a config error, got {other:?}"),
+    }
+}
+
+#[test]
+fn debug_never_shows_the_password() {
+    let mut config = ConfigInfo::default();
+    config.caldav.cal_pass = Secret::new("hunter2");
+    let debug = format!("{config:?}");
+    assert!(!debug.contains("hunter2"), "{debug}");
+    assert!(debug.contains("<redacted>"), "{debug}");
+    assert!(!format!("{config:#?}").contains("hunter2"));
+}
@@ -0,0 +1,173 @@
+use chrono::{Datelike, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use cronwave::dates::{date_to_naive, parse_datetime_in, zone};
+use proptest::prelude::*;
+
+fn utc(y: i32, m: u32, d: u32, h: u32, min: u32, s: u32) -> i64 {
+    Utc.with_ymd_and_hms(y, m, d, h, min, s)
+        .unwrap()
+        .timestamp()
+}
+
+#[test]
+fn taskwarrior_export_format_is_utc() {
+    let ts = parse_datetime_in("20250901T120000Z", &Utc).unwrap();
+    assert_eq!(ts, utc(2025, 9, 1, 12, 0, 0));
+}
+
+#[test]
+fn utc_designator_ignores_local_zone() {
+    let plus_two = FixedOffset::east_opt(2 * 3600).unwrap();
+    let ts = parse_datetime_in("2025-09-01T12:00:00Z", &plus_two).unwrap();
+    assert_eq!(ts, utc(2025, 9, 1, 12, 0, 0));
+}
+
+#[test]
+fn explicit_offsets() {
+    assert_eq!(
+        parse_datetime_in("2025-09-01T12:00:00+02:00", &Utc).unwrap(),
+        utc(2025, 9, 1, 10, 0, 0)
+    );
+    assert_eq!(
+        parse_datetime_in("20250901T120000-0530", &Utc).unwrap(),
+        utc(2025, 9, 1, 17, 30, 0)
+    );
+}
+
+#[test]
+fn floating_time_uses_given_zone() {
+    let minus_four = FixedOffset::west_opt(4 * 3600).unwrap();
+    let ts = parse_datetime_in("2025-09-01T08:00:00", &minus_four).unwrap();
+    assert_eq!(ts, utc(2025, 9, 1, 12, 0, 0));
+}
+
+#[test]
+fn bare_date_is_local_midnight() {
+    let plus_one = FixedOffset::east_opt(3600).unwrap();
+    let ts = parse_datetime_in("2025-09-01", &plus_one).unwrap();
+    assert_eq!(ts, utc(2025, 8, 31, 23, 0, 0));
+}
+
+#[test]
+fn week_dates() {
+    // ISO week 1 of 2025 starts on Monday 2024-12-30
+    assert_eq!(
+        parse_datetime_in("2025-W01-1T00:00:00Z", &Utc).unwrap(),
+        utc(2024, 12, 30, 0, 0, 0)
+    );
+    assert_eq!(
+        parse_datetime_in("2020-W53-7T09:30:00Z", &Utc).unwrap(),
+        utc(2021, 1, 3, 9, 30, 0)
+    );
+    assert_eq!(
+        parse_datetime_in("2025W365", &Utc).unwrap(),
+        utc(2025, 9, 5, 0, 0, 0)
+    );
+}
+
+#[test]
+fn ordinal_dates() {
+    assert_eq!(
+        parse_datetime_in("2025-001T00:00:00Z", &Utc).unwrap(),
+        utc(2025, 1, 1, 0, 0, 0)
+    );
+    assert_eq!(
+        parse_datetime_in("2024-366T23:59:59Z", &Utc).unwrap(),
+        utc(2024, 12, 31, 23, 59, 59)
+    );
+}
+
+#[test]
+fn invalid_dates_are_errors() {
+    assert!(parse_datetime_in("2025-02-30T00:00:00Z", &Utc).is_err());
+    assert!(parse_datetime_in("2025-W54-1T00:00:00Z", &Utc).is_err());
+    assert!(parse_datetime_in("2025-366T00:00:00Z", &Utc).is_err());
+    assert!(parse_datetime_in("not a date", &Utc).is_err());
+}
+
+#[test]
+fn dst_gap_moves_forward() {
+    let tz = chrono_tz::America::New_York;
+    // 02:30 on 2025-03-09 does not exist in New York
+    let ts = parse_datetime_in("2025-03-09T02:30:00", &tz).unwrap();
+    assert_eq!(ts, utc(2025, 3, 9, 7, 0, 0));
+}
+
+fn naive_datetime() -> impl Strategy<Value = NaiveDateTime> {
+    // 1900-01-01 through 2199-12-31
+    (-25567i64..83215, 0u32..86400).prop_map(|(days, secs)| {
+        let date = NaiveDate::from_ymd_opt(1970, 1, 1).unwrap() + chrono::Duration::days(days);
+        date.and_hms_opt(secs / 3600, secs / 60 % 60, secs % 60)
+            .unwrap()
+    })
+}
+
+proptest! {
+    #[test]
+    fn ymd_round_trips(dt in naive_datetime()) {
+        let input = dt.format("%Y%m%dT%H%M%SZ").to_string();
+        prop_assert_eq!(parse_datetime_in(&input, &Utc).unwrap(), dt.and_utc().timestamp());
+    }
+
+    #[test]
+    fn week_round_trips(dt in naive_datetime()) {
+        let week = dt.iso_week();
+        let input = format!(
+            "{:04}-W{:02}-{}T{:02}:{:02}:{:02}Z",
+            week.year(),
+            week.week(),
+            dt.weekday().number_from_monday(),
+            dt.hour(),
+            dt.minute(),
+            dt.second()
+        );
+        prop_assert_eq!(parse_datetime_in(&input, &Utc).unwrap(), dt.and_utc().timestamp());
+    }
+
+    #[test]
+    fn ordinal_round_trips(dt in naive_datetime()) {
+        let input = format!("{}T{}Z", dt.format("%Y-%j"), dt.format("%H:%M:%S"));
+        prop_assert_eq!(parse_datetime_in(&input, &Utc).unwrap(), dt.and_utc().timestamp());
+    }
+
+    #[test]
+    fn offset_round_trips(dt in naive_datetime(), minutes in -14 * 60i32..=14 * 60) {
+        let offset = FixedOffset::east_opt(minutes * 60).unwrap();
+        let expected = offset.from_local_datetime(&dt).unwrap();
+        let input = expected.format("%Y-%m-%dT%H:%M:%S%:z").to_string();
+        prop_assert_eq!(parse_datetime_in(&input, &Utc).unwrap(), expected.timestamp());
+    }
+
+    #[test]
+    fn floating_matches_chrono(dt in naive_datetime(), minutes in -14 * 60i32..=14 * 60) {
+        let offset = FixedOffset::east_opt(minutes * 60).unwrap();
+        let input = dt.format("%Y-%m-%dT%H:%M:%S").to_string();
+        prop_assert_eq!(
+            parse_datetime_in(&input, &offset).unwrap(),
+            offset.from_local_datetime(&dt).unwrap().timestamp()
+        );
+    }
+
+    #[test]
+    fn date_forms_agree(date in (-25567i64..83215).prop_map(|d| {
+        NaiveDate::from_ymd_opt(1970, 1, 1).unwrap() + chrono::Duration::days(d)
+    })) {
+        let week = date.iso_week();
+        let ymd = iso8601::Date::YMD { year: date.year(), month: date.month(), day: date.day() };
+        let ww = iso8601::Date::Week {
+            year: week.year(),
+            ww: week.week(),
+            d: date.weekday().number_from_monday(),
+        };
+        let ordinal = iso8601::Date::Ordinal { year: date.year(), ddd: date.ordinal() };
+        prop_assert_eq!(date_to_naive(ymd).unwrap(), date);
+        prop_assert_eq!(date_to_naive(ww).unwrap(), date);
+        prop_assert_eq!(date_to_naive(ordinal).unwrap(), date);
+    }
+}
+
+#[test]
+fn zone_names() {
+    assert!(zone("").unwrap().is_local());
+    assert_eq!(zone("Europe/Berlin").unwrap().name(), "Europe/Berlin");
+    assert!(zone("Mars/Olympus").is_err());
+}
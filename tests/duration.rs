@@ -0,0 +1,44 @@
+use cronwave::duration::parse_duration;
+
+const MINUTE: i64 = 60;
+const HOUR: i64 = 3600;
+const DAY: i64 = 86400;
+
+#[test]
+fn iso_8601() {
+    assert_eq!(parse_duration("PT1H30M").unwrap(), 90 * MINUTE);
+    assert_eq!(parse_duration("pt45m").unwrap(), 45 * MINUTE);
+    assert_eq!(parse_duration("P2W").unwrap(), 14 * DAY);
+    assert_eq!(parse_duration("P1DT2H").unwrap(), DAY + 2 * HOUR);
+}
+
+#[test]
+fn taskwarrior_words() {
+    assert_eq!(parse_duration("3days").unwrap(), 3 * DAY);
+    assert_eq!(parse_duration("2 weeks").unwrap(), 14 * DAY);
+    assert_eq!(parse_duration("weekly").unwrap(), 7 * DAY);
+    assert_eq!(parse_duration("monthly").unwrap(), 30 * DAY);
+}
+
+#[test]
+fn shorthand() {
+    assert_eq!(parse_duration("90min").unwrap(), 90 * MINUTE);
+    assert_eq!(parse_duration("1.5h").unwrap(), 90 * MINUTE);
+    assert_eq!(parse_duration("2d").unwrap(), 2 * DAY);
+    assert_eq!(parse_duration("1h30m").unwrap(), 90 * MINUTE);
+    assert_eq!(parse_duration(" 1h, 15m ").unwrap(), 75 * MINUTE);
+}
+
+#[test]
+fn zero_is_rejected() {
+    for input in ["0h", "0min", "PT0M", "0d 0h"] {
+        assert!(parse_duration(input).is_err(), "{input} was accepted");
+    }
+}
+
+#[test]
+fn garbage_is_rejected() {
+    for input in ["", "h", "10", "10 parsecs", "P1X"] {
+        assert!(parse_duration(input).is_err(), "{input} was accepted");
+    }
+}
@@ -0,0 +1,265 @@
+use chrono::{DateTime, TimeZone, Timelike};
+use chrono_tz::Tz;
+use cronwave::plan::{block_end, plan};
+use cronwave::structs::{ConfigInfo, TimeBlock, TimePreference};
+use cronwave::Error;
+
+mod common;
+use common::{event, monday_nine, task, HOUR};
+
+fn pieces(blocks: &[TimeBlock], uuid: &str) -> Vec<(i64, i64)> {
+    let mut pieces: Vec<(i64, i64)> = blocks
+        .iter()
+        .filter(|b| b.uid == uuid)
+        .map(|b| (b.dtstart, block_end(b)))
+        .collect();
+    pieces.sort();
+    pieces
+}
+
+#[test]
+fn earliest_due_goes_first() {
+    let now = monday_nine();
+    let tasks = vec![
+        task("a", HOUR, now + 10 * HOUR),
+        task("b", HOUR, now + 5 * HOUR),
+    ];
+    let blocks = vec![event("meeting", now + 2 * HOUR, now + 3 * HOUR)];
+    let schedule = plan(tasks, blocks, &ConfigInfo::default(), now).unwrap();
+    assert_eq!(pieces(&schedule.placed, "b"), vec![(now, now + HOUR)]);
+    assert_eq!(
+        pieces(&schedule.placed, "a"),
+        vec![(now + HOUR, now + 2 * HOUR)]
+    );
+    assert!(schedule.late.is_empty());
+}
+
+#[test]
+fn splits_around_an_event() {
+    let now = monday_nine();
+    let tasks = vec![task("long", 3 * HOUR, now + 24 * HOUR)];
+    let blocks = vec![event("meeting", now + HOUR, now + 2 * HOUR)];
+    let schedule = plan(tasks, blocks, &ConfigInfo::default(), now).unwrap();
+    assert_eq!(
+        pieces(&schedule.placed, "long"),
+        vec![(now, now + HOUR), (now + 2 * HOUR, now + 4 * HOUR)]
+    );
+}
+
+#[test]
+fn nothing_goes_inside_a_long_event() {
+    let now = monday_nine();
+    let tasks = vec![task("t", HOUR, now + 24 * HOUR)];
+    let blocks = vec![
+        event("workshop", now, now + 4 * HOUR),
+        event("talk", now + HOUR, now + 2 * HOUR),
+    ];
+    let schedule = plan(tasks, blocks, &ConfigInfo::default(), now).unwrap();
+    assert_eq!(
+        pieces(&schedule.placed, "t"),
+        vec![(now + 4 * HOUR, now + 5 * HOUR)]
+    );
+}
+
+#[test]
+fn reports_late_tasks() {
+    let now = monday_nine();
+    let tasks = vec![task("t", 2 * HOUR, now + HOUR)];
+    let schedule = plan(tasks, vec![], &ConfigInfo::default(), now).unwrap();
+    assert_eq!(schedule.late.len(), 1);
+    assert_eq!(schedule.late[0].finish, now + 2 * HOUR);
+}
+
+#[test]
+fn same_input_same_plan() {
+    let now = monday_nine();
+    let tasks = vec![
+        task("a", HOUR, now + 3 * HOUR),
+        task("b", 2 * HOUR, now + 8 * HOUR),
+    ];
+    let blocks = vec![event("lunch", now + 3 * HOUR, now + 4 * HOUR)];
+    let first = plan(tasks.clone(), blocks.clone(), &ConfigInfo::default(), now).unwrap();
+    let second = plan(tasks, blocks, &ConfigInfo::default(), now).unwrap();
+    let key = |b: &TimeBlock| (b.uid.clone(), b.dtstart, block_end(b), b.dtstamp);
+    assert_eq!(
+        first.placed.iter().map(key).collect::<Vec<_>>(),
+        second.placed.iter().map(key).collect::<Vec<_>>()
+    );
+    assert!(first
+        .placed
+        .iter()
+        .all(|b| b.dtstamp == DateTime::from_timestamp(now, 0).unwrap()));
+}
+
+fn tagged_config(tag: &str, preference: TimePreference) -> ConfigInfo {
+    ConfigInfo {
+        preferences: vec![TimePreference {
+            tag: Some(tag.to_string()),
+            ..preference
+        }],
+        ..ConfigInfo::default()
+    }
+}
+
+#[test]
+fn task_that_is_never_allowed_is_infeasible() {
+    let now = monday_nine();
+    let mut never = task("never", HOUR, now + 10 * HOUR);
+    never.tags = vec!["never".to_string()];
+    let config = tagged_config(
+        "never",
+        TimePreference {
+            forbidden: vec!["00:00-24:00".to_string()],
+            ..TimePreference::default()
+        },
+    );
+    let result = plan(vec![never], vec![], &config, now);
+    assert!(matches!(result, Err(Error::Infeasible(_))));
+}
+
+#[test]
+fn bad_window_is_a_config_error() {
+    let now = monday_nine();
+    let mut t = task("t", HOUR, now + 10 * HOUR);
+    t.tags = vec!["focus".to_string()];
+    let config = tagged_config(
+        "focus",
+        TimePreference {
+            only: vec!["9am-noon".to_string()],
+            ..TimePreference::default()
+        },
+    );
+    let result = plan(vec![t], vec![], &config, now);
+    assert!(matches!(result, Err(Error::Config(_))));
+}
+
+const BERLIN: Tz = chrono_tz::Europe::Berlin;
+
+// seconds since midnight in `zone`
+fn local_time(timestamp: i64, zone: Tz) -> u32 {
+    zone.timestamp_opt(timestamp, 0)
+        .unwrap()
+        .num_seconds_from_midnight()
+}
+
+#[test]
+fn tasks_stay_inside_working_hours() {
+    let now = monday_nine();
+    let mut config = ConfigInfo::default();
+    config.planning.working_hours = vec!["10:00-12:00".to_string()];
+    config.planning.timezone = "Europe/Berlin".to_string();
+    let tasks = vec![task("t", 3 * HOUR, now + 72 * HOUR)];
+    let schedule = plan(tasks, vec![], &config, now).unwrap();
+    let pieces = pieces(&schedule.placed, "t");
+    assert_eq!(pieces.iter().map(|(s, e)| e - s).sum::<i64>(), 3 * HOUR);
+    assert!(pieces.len() >= 2, "{pieces:?}");
+    for (start, end) in pieces {
+        assert!(
+            local_time(start, BERLIN) >= 10 * 3600,
+            "starts before 10:00"
+        );
+        assert!(local_time(end - 1, BERLIN) < 12 * 3600, "runs past 12:00");
+    }
+}
+
+#[test]
+fn own_windows_outside_working_hours_are_infeasible() {
+    let now = monday_nine();
+    let mut t = task("t", HOUR, now + 10 * HOUR);
+    t.tags = vec!["late".to_string()];
+    let mut config = tagged_config(
+        "late",
+        TimePreference {
+            only: vec!["18:00-20:00".to_string()],
+            ..TimePreference::default()
+        },
+    );
+    config.planning.working_hours = vec!["09:00-17:00".to_string()];
+    config.planning.timezone = "Europe/Berlin".to_string();
+    let result = plan(vec![t], vec![], &config, now);
+    assert!(matches!(result, Err(Error::Infeasible(_))));
+}
+
+// the same wall-clock day planned in two zones, as (uid, local start, local end)
+fn local_plan(zone: Tz) -> Vec<(String, u32, u32)> {
+    let at = |h, m| {
+        zone.with_ymd_and_hms(2025, 9, 1, h, m, 0)
+            .unwrap()
+            .timestamp()
+    };
+    let now = at(7, 0);
+    let mut deep = task("deep", HOUR, now + 48 * HOUR);
+    deep.tags = vec!["deep".to_string()];
+    let tasks = vec![deep, task("mail", HOUR / 2, now + 2 * HOUR)];
+    let blocks = vec![event("standup", at(8, 0), at(8, 30))];
+    let mut config = tagged_config(
+        "deep",
+        TimePreference {
+            only: vec!["weekdays 09:00-11:00".to_string()],
+            ..TimePreference::default()
+        },
+    );
+    config.planning.working_hours = vec!["weekdays 08:00-17:00".to_string()];
+    config.planning.timezone = zone.name().to_string();
+    let schedule = plan(tasks, blocks, &config, now).unwrap();
+    let mut placed: Vec<(String, u32, u32)> = schedule
+        .placed
+        .iter()
+        .map(|b| {
+            (
+                b.uid.clone(),
+                local_time(b.dtstart, zone),
+                local_time(block_end(b), zone),
+            )
+        })
+        .collect();
+    placed.sort();
+    placed
+}
+
+#[test]
+fn windows_follow_the_configured_zone() {
+    let berlin = local_plan(BERLIN);
+    assert_eq!(berlin, local_plan(chrono_tz::America::New_York));
+    assert_eq!(
+        berlin,
+        vec![
+            ("deep".to_string(), 9 * 3600, 10 * 3600),
+            ("mail".to_string(), 8 * 3600 + 1800, 9 * 3600),
+        ]
+    );
+}
+
+// a heavy task that fits the morning's high energy and a light one due before the morning ends
+fn energy_and_deadline(report_due: i64) -> Vec<(i64, i64)> {
+    let now = monday_nine();
+    let mut deep = task("deep", 2 * HOUR, now + 72 * HOUR);
+    deep.energy = Some("high".to_string());
+    let mut report = task("report", 2 * HOUR, report_due);
+    report.energy = Some("low".to_string());
+    let mut config = ConfigInfo::default();
+    config.planning.timezone = "UTC".to_string();
+    config.energy.high = vec!["09:00-11:00".to_string()];
+    let schedule = plan(vec![deep, report], vec![], &config, now).unwrap();
+    assert!(schedule.late.is_empty(), "{:?}", schedule.late);
+    pieces(&schedule.placed, "deep")
+}
+
+#[test]
+fn deadline_beats_energy() {
+    let now = monday_nine();
+    // the report only makes it when it goes first, into the high energy window
+    assert_eq!(
+        energy_and_deadline(now + 2 * HOUR),
+        vec![(now + 2 * HOUR, now + 4 * HOUR)]
+    );
+}
+
+#[test]
+fn energy_decides_when_deadlines_allow() {
+    let now = monday_nine();
+    assert_eq!(
+        energy_and_deadline(now + 48 * HOUR),
+        vec![(now, now + 2 * HOUR)]
+    );
+}
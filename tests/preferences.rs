@@ -0,0 +1,102 @@
+use chrono::{TimeZone, Utc};
+use cronwave::dates::zone;
+use cronwave::preferences::{for_task, Constraints, Window};
+use cronwave::structs::{Task, TimePreference};
+
+const HOUR: i64 = 3600;
+
+// Monday 2025-09-01 at `hour` UTC
+fn monday(hour: u32, minute: u32) -> i64 {
+    Utc.with_ymd_and_hms(2025, 9, 1, hour, minute, 0)
+        .unwrap()
+        .timestamp()
+}
+
+fn tagged(tag: &str) -> Task {
+    Task {
+        id: 0,
+        uuid: "t".to_string(),
+        description: "t".to_string(),
+        due: 0,
+        estimated: HOUR,
+        status: "pending".to_string(),
+        urgency: 0.0,
+        start: None,
+        project: None,
+        tags: vec![tag.to_string()],
+        three_point: None,
+        energy: None,
+    }
+}
+
+fn rules(only: &[&str], forbidden: &[&str]) -> Constraints {
+    let preference = TimePreference {
+        tag: Some("focus".to_string()),
+        only: only.iter().map(|w| w.to_string()).collect(),
+        forbidden: forbidden.iter().map(|w| w.to_string()).collect(),
+        ..TimePreference::default()
+    };
+    for_task(&[preference], &tagged("focus"), zone("UTC").unwrap()).unwrap()
+}
+
+fn at(timestamp: i64) -> chrono::DateTime<Utc> {
+    Utc.timestamp_opt(timestamp, 0).unwrap()
+}
+
+#[test]
+fn day_ranges_may_have_spaces() {
+    let window = Window::parse("mon - fri 08:00-12:00").unwrap();
+    assert!(window.contains(&at(monday(9, 0))));
+    assert!(!window.contains(&at(monday(9, 0) + 5 * 24 * HOUR)));
+    assert!(Window::parse("sat, sun 10:00-11:00").is_ok());
+}
+
+#[test]
+fn windows_past_midnight() {
+    let window = Window::parse("fri 22:00-02:00").unwrap();
+    let friday = monday(0, 0) + 4 * 24 * HOUR;
+    assert!(window.contains(&at(friday + 23 * HOUR)));
+    assert!(window.contains(&at(friday + 25 * HOUR)));
+    assert!(!window.contains(&at(friday + HOUR)));
+    assert!(!window.contains(&at(friday + 27 * HOUR)));
+}
+
+#[test]
+fn bad_windows_are_rejected() {
+    assert!(Window::parse("08:00").is_err());
+    assert!(Window::parse("funday 08:00-09:00").is_err());
+    assert!(Window::parse("25:00-26:00").is_err());
+}
+
+#[test]
+fn forbidden_cuts_into_only() {
+    let rules = rules(&["09:00-11:00"], &["10:00-10:30"]);
+    assert!(!rules.allowed_at(monday(8, 59)));
+    assert!(rules.allowed_at(monday(9, 0)));
+    assert!(!rules.allowed_at(monday(10, 15)));
+    assert!(rules.allowed_at(monday(10, 30)));
+    assert!(!rules.allowed_at(monday(11, 0)));
+    assert_eq!(
+        rules.allowed_until(monday(9, 0), monday(12, 0)),
+        monday(10, 0)
+    );
+}
+
+#[test]
+fn next_allowed_skips_to_the_window() {
+    let rules = rules(&["09:00-11:00"], &["10:00-10:30"]);
+    assert_eq!(rules.next_allowed(monday(8, 0)), Some(monday(9, 0)));
+    assert_eq!(rules.next_allowed(monday(10, 10)), Some(monday(10, 30)));
+    assert_eq!(rules.next_allowed(monday(9, 30)), Some(monday(9, 30)));
+    // the next day's window
+    assert_eq!(
+        rules.next_allowed(monday(12, 0)),
+        Some(monday(9, 0) + 24 * HOUR)
+    );
+}
+
+#[test]
+fn next_allowed_is_none_when_never_allowed() {
+    let rules = rules(&["09:00-10:00"], &["08:00-11:00"]);
+    assert_eq!(rules.next_allowed(monday(8, 0)), None);
+}
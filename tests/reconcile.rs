@@ -0,0 +1,104 @@
+use cronwave::plan::block_end;
+use cronwave::reconcile::{reconcile, Reconciled};
+use cronwave::structs::{TaskState, TimeBlock};
+use std::collections::HashMap;
+
+mod common;
+use common::{event, monday_nine, HOUR};
+
+fn block(uid: &str, task: Option<&str>, start: i64, end: i64) -> TimeBlock {
+    TimeBlock {
+        task_uuid: task.map(str::to_string),
+        ..event(uid, start, end)
+    }
+}
+
+fn states(entries: &[(&str, &str, Option<&str>)]) -> HashMap<String, TaskState> {
+    entries
+        .iter()
+        .map(|(uuid, status, end)| {
+            (
+                uuid.to_string(),
+                TaskState {
+                    uuid: uuid.to_string(),
+                    status: status.to_string(),
+                    end: end.map(str::to_string),
+                },
+            )
+        })
+        .collect()
+}
+
+fn spans(blocks: &[TimeBlock]) -> Vec<(String, i64, i64)> {
+    blocks
+        .iter()
+        .map(|b| (b.uid.clone(), b.dtstart, block_end(b)))
+        .collect()
+}
+
+#[test]
+fn completed_task_keeps_history_and_frees_the_rest() {
+    let nine = monday_nine();
+    // finished at 10:30
+    let states = states(&[("t", "completed", Some("20250901T103000Z"))]);
+    let mut blocks = vec![
+        block("past", Some("t"), nine, nine + HOUR),
+        block("running", Some("t"), nine + HOUR, nine + 2 * HOUR),
+        block("future", Some("t"), nine + 3 * HOUR, nine + 4 * HOUR),
+    ];
+    let result = reconcile(&mut blocks, &states, nine + 5 * HOUR);
+    assert_eq!(
+        result,
+        Reconciled {
+            removed: 1,
+            shrunk: 1,
+            freed: HOUR + HOUR / 2,
+        }
+    );
+    assert_eq!(
+        spans(&blocks),
+        vec![
+            ("past".to_string(), nine, nine + HOUR),
+            ("running".to_string(), nine + HOUR, nine + HOUR + HOUR / 2),
+        ]
+    );
+}
+
+#[test]
+fn missing_task_is_removed_from_now_on() {
+    let nine = monday_nine();
+    let mut blocks = vec![block("gone", Some("gone"), nine + HOUR, nine + 2 * HOUR)];
+    let result = reconcile(&mut blocks, &HashMap::new(), nine);
+    assert_eq!(result.removed, 1);
+    assert!(blocks.is_empty());
+}
+
+#[test]
+fn pending_tasks_and_other_events_are_left_alone() {
+    let nine = monday_nine();
+    let states = states(&[("t", "pending", None)]);
+    let mut blocks = vec![
+        block("t", Some("t"), nine + HOUR, nine + 2 * HOUR),
+        block("meeting", None, nine + 2 * HOUR, nine + 3 * HOUR),
+    ];
+    let result = reconcile(&mut blocks, &states, nine);
+    assert_eq!(result, Reconciled::default());
+    assert_eq!(blocks.len(), 2);
+}
+
+#[test]
+fn batch_goes_once_every_member_is_done() {
+    let nine = monday_nine();
+    let mut batch = block("batch", Some("a"), nine + HOUR, nine + 2 * HOUR);
+    batch.batch = vec!["a".to_string(), "b".to_string()];
+
+    let half_done = states(&[("a", "completed", None), ("b", "pending", None)]);
+    let mut blocks = vec![batch.clone()];
+    reconcile(&mut blocks, &half_done, nine);
+    assert_eq!(blocks.len(), 1);
+
+    let all_done = states(&[("a", "completed", None), ("b", "deleted", None)]);
+    let mut blocks = vec![batch];
+    reconcile(&mut blocks, &all_done, nine);
+    assert!(blocks.is_empty());
+}
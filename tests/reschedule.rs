@@ -0,0 +1,280 @@
+use cronwave::plan::{block_end, reschedule, subtract_locked, Moves, PIN_TAG};
+use cronwave::structs::{ConfigInfo, TimeBlock};
+
+mod common;
+use common::{event, monday_nine, task, task_block, HOUR};
+
+type Pieces = Vec<(i64, i64)>;
+
+// where each of the task's blocks sits after the reschedule, kept or placed
+fn pieces(rescheduled: &cronwave::plan::Rescheduled, uuid: &str) -> Pieces {
+    let mut pieces: Pieces = rescheduled
+        .kept
+        .iter()
+        .chain(&rescheduled.schedule.placed)
+        .filter(|b| b.uid == uuid)
+        .map(|b| (b.dtstart, block_end(b)))
+        .collect();
+    pieces.sort();
+    pieces
+}
+
+#[test]
+fn clashing_block_moves_past_the_new_event() {
+    let now = monday_nine();
+    let tasks = vec![task("t", HOUR, now + 24 * HOUR)];
+    let blocks = vec![
+        task_block("t", now + HOUR, now + 2 * HOUR),
+        event("meeting", now + HOUR, now + 2 * HOUR),
+    ];
+    let done = reschedule(blocks, &tasks, &ConfigInfo::default(), now).unwrap();
+    assert_eq!(pieces(&done, "t"), vec![(now + 2 * HOUR, now + 3 * HOUR)]);
+    assert_eq!(
+        done.moves,
+        Moves {
+            moved: 1,
+            blocks: 1,
+            average: HOUR,
+            max: HOUR,
+        }
+    );
+}
+
+#[test]
+fn frozen_blocks_stay() {
+    let now = monday_nine();
+    let tasks = vec![task("t", HOUR, now + 24 * HOUR)];
+    let blocks = vec![
+        task_block("t", now + HOUR, now + 2 * HOUR),
+        event("meeting", now + HOUR, now + 2 * HOUR),
+    ];
+    let mut config = ConfigInfo::default();
+    config.reschedule.freeze_hours = 2;
+    let done = reschedule(blocks, &tasks, &config, now).unwrap();
+    assert_eq!(done.kept.len(), 1);
+    assert_eq!(pieces(&done, "t"), vec![(now + HOUR, now + 2 * HOUR)]);
+    assert_eq!(done.moves.moved, 0);
+}
+
+// `a` clashes with a new meeting, `b` still fits right after it
+fn stable_case(stable: bool) -> (Pieces, Pieces) {
+    let now = monday_nine();
+    let tasks = vec![
+        task("a", HOUR, now + 3 * HOUR),
+        task("b", HOUR, now + 24 * HOUR),
+    ];
+    let blocks = vec![
+        task_block("a", now + HOUR, now + 2 * HOUR),
+        event("meeting", now + HOUR, now + 2 * HOUR),
+        task_block("b", now + 2 * HOUR, now + 3 * HOUR),
+    ];
+    let mut config = ConfigInfo::default();
+    config.reschedule.stable = stable;
+    let done = reschedule(blocks, &tasks, &config, now).unwrap();
+    (pieces(&done, "a"), pieces(&done, "b"))
+}
+
+#[test]
+fn stable_mode_keeps_blocks_that_still_fit() {
+    let now = monday_nine();
+    let (a, b) = stable_case(true);
+    assert_eq!(b, vec![(now + 2 * HOUR, now + 3 * HOUR)]);
+    assert_eq!(a, vec![(now + 3 * HOUR, now + 4 * HOUR)]);
+}
+
+#[test]
+fn without_stable_mode_everything_is_replanned() {
+    let now = monday_nine();
+    let (a, b) = stable_case(false);
+    assert_eq!(a, vec![(now + 2 * HOUR, now + 3 * HOUR)]);
+    assert_eq!(b, vec![(now + 3 * HOUR, now + 4 * HOUR)]);
+}
+
+#[test]
+fn locked_and_pinned_blocks_stay() {
+    let now = monday_nine();
+    let mut pinned = task("pinned", HOUR, now + 24 * HOUR);
+    pinned.tags = vec![PIN_TAG.to_string()];
+    let tasks = vec![task("locked", HOUR, now + 24 * HOUR), pinned];
+    let blocks = vec![
+        TimeBlock {
+            locked: true,
+            ..task_block("locked", now + HOUR, now + 2 * HOUR)
+        },
+        task_block("pinned", now + HOUR, now + 2 * HOUR),
+        event("meeting", now + HOUR, now + 2 * HOUR),
+    ];
+    let done = reschedule(blocks, &tasks, &ConfigInfo::default(), now).unwrap();
+    assert_eq!(done.kept.len(), 2);
+    assert!(done.schedule.placed.is_empty());
+}
+
+#[test]
+fn locked_time_is_not_counted_twice() {
+    let now = monday_nine();
+    let tasks = vec![task("t", 2 * HOUR, now + 24 * HOUR)];
+    let blocks = vec![
+        TimeBlock {
+            locked: true,
+            ..task_block("t", now, now + HOUR)
+        },
+        task_block("t", now + HOUR, now + 2 * HOUR),
+        event("meeting", now + HOUR, now + 2 * HOUR),
+    ];
+    let done = reschedule(blocks, &tasks, &ConfigInfo::default(), now).unwrap();
+    let total: i64 = pieces(&done, "t").iter().map(|(s, e)| e - s).sum();
+    assert_eq!(total, 2 * HOUR);
+}
+
+#[test]
+fn locked_time_comes_off_the_estimate() {
+    let now = monday_nine();
+    let mut tasks = vec![
+        task("partly", 2 * HOUR, now + 24 * HOUR),
+        task("covered", HOUR, now + 24 * HOUR),
+        task("free", HOUR, now + 24 * HOUR),
+    ];
+    let blocks = vec![
+        TimeBlock {
+            locked: true,
+            ..task_block("partly", now, now + HOUR / 2)
+        },
+        // matched through X-CRONWAVE-TASK even when the UID differs
+        TimeBlock {
+            locked: true,
+            uid: "other-uid".to_string(),
+            ..task_block("covered", now + HOUR, now + 2 * HOUR)
+        },
+        task_block("free", now + 2 * HOUR, now + 3 * HOUR),
+    ];
+    subtract_locked(&mut tasks, &blocks);
+    let left: Vec<(&str, i64)> = tasks
+        .iter()
+        .map(|t| (t.uuid.as_str(), t.estimated))
+        .collect();
+    assert_eq!(left, vec![("partly", 3 * HOUR / 2), ("free", HOUR)]);
+}
+
+#[test]
+fn moves_pair_blocks_in_start_order() {
+    let now = monday_nine();
+    let before = vec![
+        task_block("t", now, now + HOUR),
+        task_block("t", now + 2 * HOUR, now + 3 * HOUR),
+    ];
+    let after = vec![
+        task_block("t", now + 2 * HOUR + HOUR / 2, now + 3 * HOUR + HOUR / 2),
+        task_block("t", now, now + HOUR),
+    ];
+    assert_eq!(
+        Moves::between(&before, &after),
+        Moves {
+            moved: 1,
+            blocks: 2,
+            average: HOUR / 2,
+            max: HOUR / 2,
+        }
+    );
+}
+
+#[test]
+fn foreign_block_with_the_same_uid_stays_busy() {
+    let now = monday_nine();
+    let tasks = vec![task("t", HOUR, now + 24 * HOUR)];
+    // a shared meeting on both calendars, and this profile's block clashing with it
+    let shared = TimeBlock {
+        foreign: true,
+        ..event("shared", now + HOUR, now + 2 * HOUR)
+    };
+    let theirs = TimeBlock {
+        foreign: true,
+        ..task_block("t", now + 3 * HOUR, now + 4 * HOUR)
+    };
+    let blocks = vec![
+        task_block("t", now + HOUR, now + 2 * HOUR),
+        shared,
+        event("shared", now + HOUR, now + 2 * HOUR),
+        theirs,
+    ];
+    let done = reschedule(blocks, &tasks, &ConfigInfo::default(), now).unwrap();
+    // the other profile's block of `t` is busy time, not this task's block
+    assert_eq!(pieces(&done, "t"), vec![(now + 2 * HOUR, now + 3 * HOUR)]);
+    let calendar = &done.schedule.calendar;
+    assert_eq!(calendar.iter().filter(|b| b.foreign).count(), 2);
+    // the copy of the meeting on this calendar is still there to be written back
+    assert!(calendar.iter().any(|b| b.uid == "shared" && !b.foreign));
+}
+
+#[test]
+fn unchanged_calendar_stays_put() {
+    let now = monday_nine();
+    let tasks = vec![
+        task("a", HOUR, now + 24 * HOUR),
+        task("b", 2 * HOUR, now + 24 * HOUR),
+    ];
+    let mut blocks = vec![
+        event("meeting", now, now + HOUR),
+        task_block("a", now + HOUR, now + 2 * HOUR),
+        task_block("b", now + 3 * HOUR, now + 5 * HOUR),
+    ];
+    // without stable mode every block is let go and placed again
+    for _ in 0..3 {
+        let done = reschedule(blocks, &tasks, &ConfigInfo::default(), now).unwrap();
+        assert_eq!(done.moves.moved, 0);
+        assert_eq!(done.moves.average, 0);
+        assert_eq!(pieces(&done, "a"), vec![(now + HOUR, now + 2 * HOUR)]);
+        assert_eq!(pieces(&done, "b"), vec![(now + 3 * HOUR, now + 5 * HOUR)]);
+        blocks = done.schedule.calendar;
+    }
+}
+
+#[test]
+fn raised_estimate_gets_the_extra_time() {
+    let now = monday_nine();
+    let tasks = vec![task("t", 3 * HOUR, now + 24 * HOUR)];
+    let blocks = vec![task_block("t", now + HOUR, now + 2 * HOUR)];
+    let mut config = ConfigInfo::default();
+    config.reschedule.stable = true;
+    let done = reschedule(blocks, &tasks, &config, now).unwrap();
+    let total: i64 = pieces(&done, "t").iter().map(|(s, e)| e - s).sum();
+    assert_eq!(total, 3 * HOUR);
+    // the block that still fits stays where it was
+    assert!(pieces(&done, "t").contains(&(now + HOUR, now + 2 * HOUR)));
+}
+
+#[test]
+fn lowered_estimate_trims_the_latest_blocks() {
+    let now = monday_nine();
+    let tasks = vec![task("t", HOUR + HOUR / 2, now + 24 * HOUR)];
+    let blocks = || {
+        vec![
+            task_block("t", now + HOUR, now + 2 * HOUR),
+            task_block("t", now + 3 * HOUR, now + 4 * HOUR),
+        ]
+    };
+    for stable in [false, true] {
+        let mut config = ConfigInfo::default();
+        config.reschedule.stable = stable;
+        let done = reschedule(blocks(), &tasks, &config, now).unwrap();
+        assert_eq!(
+            pieces(&done, "t"),
+            vec![
+                (now + HOUR, now + 2 * HOUR),
+                (now + 3 * HOUR, now + 3 * HOUR + HOUR / 2)
+            ],
+            "stable: {stable}"
+        );
+    }
+}
+
+#[test]
+fn lowered_estimate_leaves_locked_blocks_alone() {
+    let now = monday_nine();
+    let tasks = vec![task("t", HOUR, now + 24 * HOUR)];
+    let locked = TimeBlock {
+        locked: true,
+        ..task_block("t", now + 3 * HOUR, now + 5 * HOUR)
+    };
+    let done = reschedule(vec![locked], &tasks, &ConfigInfo::default(), now).unwrap();
+    assert_eq!(pieces(&done, "t"), vec![(now + 3 * HOUR, now + 5 * HOUR)]);
+}
@@ -0,0 +1,49 @@
+use cronwave::risk::finishes;
+use cronwave::structs::*;
+
+mod common;
+use common::task;
+
+// Monday 2025-09-01 09:00 UTC
+const NOW: i64 = 1_756_717_200;
+
+fn tasks() -> Vec<Task> {
+    vec![
+        task("a", 3600, NOW + 86_400),
+        task("b", 7200, NOW + 2 * 86_400),
+        task("c", 1800, NOW + 3 * 86_400),
+    ]
+}
+
+#[test]
+fn same_seed_gives_same_finishes() {
+    let config = ConfigInfo::default();
+    let first = finishes(&tasks(), vec![], 200, &config, NOW, 42).unwrap();
+    let second = finishes(&tasks(), vec![], 200, &config, NOW, 42).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(first["a"].len(), 200);
+}
+
+#[test]
+fn other_seed_draws_differently() {
+    let config = ConfigInfo::default();
+    let first = finishes(&tasks(), vec![], 200, &config, NOW, 42).unwrap();
+    let second = finishes(&tasks(), vec![], 200, &config, NOW, 7).unwrap();
+    assert_ne!(first, second);
+}
+
+#[test]
+fn finishes_stay_within_the_three_point_range() {
+    let config = ConfigInfo::default();
+    let mut only = task("a", 3600, NOW + 86_400);
+    only.three_point = Some(ThreePoint {
+        optimistic: 1800,
+        likely: 3600,
+        pessimistic: 7200,
+    });
+    let runs = finishes(&[only], vec![], 500, &config, NOW, 1).unwrap();
+    let runs = &runs["a"];
+    assert!(runs.windows(2).all(|w| w[0] <= w[1]));
+    assert!(runs[0] >= NOW + 1800);
+    assert!(runs[runs.len() - 1] <= NOW + 7200);
+}
@@ -0,0 +1,121 @@
+use cronwave::plan::{block_end, plan};
+use cronwave::structs::{ConfigInfo, Task, TimeBlock, TimePreference};
+use cronwave::Error;
+use proptest::prelude::*;
+
+mod common;
+use common::{event, monday_nine, task, HOUR};
+
+fn optimal() -> ConfigInfo {
+    let mut config = ConfigInfo::default();
+    config.solver.mode = "optimal".to_string();
+    config.solver.time_budget_ms = 50;
+    config.planning.timezone = "UTC".to_string();
+    config
+}
+
+#[test]
+fn no_worse_than_greedy_around_a_meeting() {
+    let now = monday_nine();
+    // the long task is due first but only fits whole after the meeting
+    let tasks = vec![
+        task("long", 2 * HOUR, now + 5 * HOUR),
+        task("short", HOUR, now + 6 * HOUR),
+    ];
+    let blocks = vec![event("meeting", now + HOUR, now + 2 * HOUR)];
+    let schedule = plan(tasks, blocks, &optimal(), now).unwrap();
+    let report = schedule.solver.unwrap();
+    assert!(report.cost <= report.greedy_cost, "{report:?}");
+    assert!(schedule.late.is_empty());
+}
+
+#[test]
+fn keeps_to_windows() {
+    let now = monday_nine();
+    let mut focus = task("focus", 2 * HOUR, now + 4 * HOUR);
+    focus.tags = vec!["focus".to_string()];
+    let mut config = optimal();
+    config.preferences = vec![TimePreference {
+        tag: Some("focus".to_string()),
+        only: vec!["13:00-14:00".to_string()],
+        ..TimePreference::default()
+    }];
+    let tasks = vec![focus, task("other", HOUR, now + 2 * HOUR)];
+    let schedule = plan(tasks, vec![], &config, now).unwrap();
+    let focus: Vec<_> = schedule
+        .placed
+        .iter()
+        .filter(|b| b.uid == "focus")
+        .collect();
+    assert_eq!(focus.len(), 2);
+    for block in focus {
+        let hour = (block.dtstart - now) / HOUR + 9;
+        assert_eq!(hour % 24, 13, "{block:?}");
+        assert_eq!(block_end(block) - block.dtstart, HOUR);
+    }
+}
+
+#[test]
+fn finds_the_better_order_across_working_days() {
+    let now = monday_nine() + HOUR;
+    let mut config = optimal();
+    config.planning.working_hours = vec!["09:00-17:00".to_string()];
+    // neither fits today, but the short one does if it goes first
+    let tasks = vec![
+        task("long", 8 * HOUR, now + 8 * HOUR),
+        task("short", HOUR, now + 8 * HOUR),
+    ];
+    let schedule = plan(tasks, vec![], &config, now).unwrap();
+    let report = schedule.solver.unwrap();
+    assert!(report.improved, "{report:?}");
+    // the long task runs into tomorrow either way, the short one need not
+    assert_eq!(report.cost, 17 * HOUR + 900, "{report:?}");
+    let short = schedule.placed.iter().find(|b| b.uid == "short").unwrap();
+    assert_eq!(short.dtstart, now);
+}
+
+#[test]
+fn refuses_heavy_tasks_it_cannot_cap() {
+    let now = monday_nine();
+    let mut heavy = task("heavy", HOUR, now + 4 * HOUR);
+    heavy.energy = Some("high".to_string());
+    let result = plan(vec![heavy], vec![], &optimal(), now);
+    assert!(matches!(result, Err(Error::Config(_))));
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn never_costs_more_than_greedy(
+        specs in prop::collection::vec((1i64..12, 1i64..48), 1..5),
+        meetings in prop::collection::vec((0i64..24, 1i64..4), 0..4),
+    ) {
+        let now = monday_nine();
+        let tasks: Vec<Task> = specs
+            .iter()
+            .enumerate()
+            .map(|(i, (quarters, due))| task(&format!("t{i}"), quarters * 900, now + due * HOUR))
+            .collect();
+        let blocks: Vec<TimeBlock> = meetings
+            .iter()
+            .enumerate()
+            .map(|(i, (start, len))| {
+                let start = now + start * HOUR;
+                event(&format!("m{i}"), start, start + len * HOUR)
+            })
+            .collect();
+        let schedule = plan(tasks.clone(), blocks, &optimal(), now).unwrap();
+        let report = schedule.solver.unwrap();
+        prop_assert!(report.cost <= report.greedy_cost);
+        for task in &tasks {
+            let placed: i64 = schedule
+                .placed
+                .iter()
+                .filter(|b| b.uid == task.uuid)
+                .map(|b| block_end(b) - b.dtstart)
+                .sum();
+            prop_assert_eq!(placed, task.estimated);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use cronwave::dates::Tz;
+use cronwave::structs::{Gap, TimeBlock, Travel};
+use cronwave::travel::{Places, Stay};
+
+mod common;
+use common::{event, HOUR, MINUTE};
+
+// Monday 2025-09-01 09:00 UTC
+const NOW: i64 = 1_756_717_200;
+
+fn located(uid: &str, start: i64, end: i64, location: Option<&str>) -> TimeBlock {
+    TimeBlock {
+        location: location.map(str::to_string),
+        ..event(uid, start, end)
+    }
+}
+
+fn places(default_place: Option<&str>, times: &[(&str, i64)]) -> Places {
+    Places::new(&Travel {
+        places: vec!["home".to_string(), "office".to_string()],
+        default_place: default_place.map(str::to_string),
+        buffer_minutes: 15,
+        times: times
+            .iter()
+            .map(|(pair, minutes)| (pair.to_string(), *minutes))
+            .collect::<HashMap<_, _>>(),
+    })
+}
+
+// the gap between the two events, after travel is taken out
+fn apply(places: &Places, blocks: &[TimeBlock]) -> (Vec<Gap>, Vec<Stay>) {
+    let mut gaps = vec![Gap {
+        start: NOW + HOUR,
+        end: NOW + 3 * HOUR,
+    }];
+    let stays = places.apply(&mut gaps, blocks, NOW + 4 * HOUR, Tz::UTC);
+    (gaps, stays)
+}
+
+fn stays_at(stays: &[Stay], place: &str) -> Vec<(i64, i64)> {
+    stays
+        .iter()
+        .filter(|s| s.place.as_deref() == Some(place) && s.end != i64::MAX)
+        .map(|s| (s.start, s.end))
+        .collect()
+}
+
+#[test]
+fn same_place_needs_no_travel() {
+    let blocks = vec![
+        located("a", NOW, NOW + HOUR, Some("Office 3.14")),
+        located("b", NOW + 3 * HOUR, NOW + 4 * HOUR, Some("office")),
+    ];
+    let (gaps, stays) = apply(&places(None, &[]), &blocks);
+    assert_eq!((gaps[0].start, gaps[0].end), (NOW + HOUR, NOW + 3 * HOUR));
+    assert_eq!(
+        stays_at(&stays, "office"),
+        vec![(NOW + HOUR, NOW + 3 * HOUR)]
+    );
+}
+
+#[test]
+fn travel_is_kept_at_both_edges() {
+    let blocks = vec![
+        located("a", NOW, NOW + HOUR, Some("home")),
+        located("b", NOW + 3 * HOUR, NOW + 4 * HOUR, Some("office")),
+    ];
+    let (gaps, stays) = apply(&places(None, &[]), &blocks);
+    let end = NOW + 3 * HOUR - 15 * MINUTE;
+    assert_eq!((gaps[0].start, gaps[0].end), (NOW + HOUR, end));
+    // home work has to stop in time to leave, office work can start on arrival
+    assert_eq!(stays_at(&stays, "home"), vec![(NOW + HOUR, end)]);
+    assert_eq!(
+        stays_at(&stays, "office"),
+        vec![(NOW + HOUR + 15 * MINUTE, NOW + 3 * HOUR)]
+    );
+}
+
+#[test]
+fn pair_time_overrides_the_buffer() {
+    let blocks = vec![
+        located("a", NOW, NOW + HOUR, Some("office")),
+        located("b", NOW + 3 * HOUR, NOW + 4 * HOUR, Some("home")),
+    ];
+    let (gaps, stays) = apply(&places(None, &[("home-office", 40)]), &blocks);
+    let end = NOW + 3 * HOUR - 40 * MINUTE;
+    assert_eq!(gaps[0].end, end);
+    assert_eq!(
+        stays_at(&stays, "home"),
+        vec![(NOW + HOUR + 40 * MINUTE, NOW + 3 * HOUR)]
+    );
+}
+
+#[test]
+fn default_place_counts_as_where_you_start() {
+    let blocks = vec![
+        located("a", NOW, NOW + HOUR, None),
+        located("b", NOW + 3 * HOUR, NOW + 4 * HOUR, Some("office")),
+    ];
+    let (gaps, stays) = apply(&places(Some("home"), &[]), &blocks);
+    let end = NOW + 3 * HOUR - 15 * MINUTE;
+    assert_eq!(gaps[0].end, end);
+    assert_eq!(stays_at(&stays, "home"), vec![(NOW + HOUR, end)]);
+
+    // without a default nothing says you have to travel
+    let (gaps, stays) = apply(&places(None, &[]), &blocks);
+    assert_eq!(gaps[0].end, NOW + 3 * HOUR);
+    assert_eq!(
+        stays_at(&stays, "office"),
+        vec![(NOW + HOUR, NOW + 3 * HOUR)]
+    );
+}
+
+#[test]
+fn gap_shorter_than_the_trip_is_dropped() {
+    let blocks = vec![
+        located("a", NOW, NOW + HOUR, Some("home")),
+        located("b", NOW + 3 * HOUR, NOW + 4 * HOUR, Some("office")),
+    ];
+    let (gaps, stays) = apply(&places(None, &[("home-office", 120)]), &blocks);
+    assert!(gaps.is_empty());
+    assert!(stays_at(&stays, "home").is_empty());
+    // the overflow carries on at the last place
+    assert_eq!(stays.last().unwrap().place.as_deref(), Some("office"));
+}
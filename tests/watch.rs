@@ -0,0 +1,100 @@
+use cronwave::watch::{decide, Decision, ADDED};
+use serde_json::{json, Value};
+
+fn pending() -> Value {
+    json!({
+        "uuid": "a",
+        "description": "write report",
+        "status": "pending",
+        "estimated": "PT2H",
+        "due": "20250905T170000Z",
+    })
+}
+
+fn tagged(mut task: Value, tag: &str) -> Value {
+    task["tags"] = json!([tag]);
+    task
+}
+
+#[test]
+fn added_task_with_an_estimate_is_queued() {
+    let decision = decide(None, &pending()).unwrap();
+    assert!(decision.tag_unscheduled);
+    assert_eq!(decision.queue.as_deref(), Some(ADDED));
+}
+
+#[test]
+fn added_task_without_an_estimate_is_left_alone() {
+    let task = json!({"uuid": "a", "description": "call", "status": "pending"});
+    assert_eq!(decide(None, &task).unwrap(), Decision::default());
+}
+
+#[test]
+fn bad_estimate_is_rejected() {
+    let mut task = pending();
+    task["estimated"] = json!("soon");
+    let err = decide(None, &task).unwrap_err();
+    assert!(err.contains("write report"), "{err}");
+    // the same goes for a change that breaks the estimate
+    assert!(decide(Some(&pending()), &task).is_err());
+}
+
+#[test]
+fn estimate_without_due_is_rejected() {
+    let mut task = pending();
+    task.as_object_mut().unwrap().remove("due");
+    assert!(decide(None, &task).is_err());
+}
+
+#[test]
+fn finishing_a_task_always_goes_through() {
+    let original = tagged(json!({"uuid": "a", "estimated": "soon"}), "scheduled");
+    let mut done = original.clone();
+    done["status"] = json!("completed");
+    let decision = decide(Some(&original), &done).unwrap();
+    assert!(!decision.tag_unscheduled);
+    assert_eq!(decision.queue.as_deref(), Some("status"));
+}
+
+#[test]
+fn estimate_added_later_is_queued_as_new() {
+    let mut original = pending();
+    original.as_object_mut().unwrap().remove("estimated");
+    let decision = decide(Some(&original), &pending()).unwrap();
+    assert!(decision.tag_unscheduled);
+    assert_eq!(decision.queue.as_deref(), Some(ADDED));
+}
+
+#[test]
+fn changed_due_on_a_scheduled_task_queues_a_reschedule() {
+    let original = tagged(pending(), "scheduled");
+    let mut modified = original.clone();
+    modified["due"] = json!("20250908T170000Z");
+    modified["estimated"] = json!("PT3H");
+    let decision = decide(Some(&original), &modified).unwrap();
+    assert!(!decision.tag_unscheduled);
+    assert_eq!(decision.queue.as_deref(), Some("due,estimated"));
+}
+
+#[test]
+fn unwatched_change_is_not_queued() {
+    let original = tagged(pending(), "scheduled");
+    let mut modified = original.clone();
+    modified["description"] = json!("write the report");
+    assert_eq!(
+        decide(Some(&original), &modified).unwrap(),
+        Decision::default()
+    );
+}
+
+#[test]
+fn untracked_task_is_not_queued() {
+    let mut original = pending();
+    original.as_object_mut().unwrap().remove("estimated");
+    let mut modified = original.clone();
+    modified["due"] = json!("20250908T170000Z");
+    assert_eq!(
+        decide(Some(&original), &modified).unwrap(),
+        Decision::default()
+    );
+}